@@ -0,0 +1,355 @@
+//! Replays recorded market data through the sizing of Alice to evaluate a
+//! strategy offline, see `src/bin/backtest.rs`.
+
+use crate::ics_pool::PublicPoolOverView;
+use crate::paper::{value_in_icp, PaperPortfolio};
+use crate::state::{Quote, State};
+use crate::{
+    parse_answer, trade_action, Decision, DecisionRecord, Token, TradeAction, MIN_PRICE_HISTORY,
+};
+use candid::Deserialize;
+use std::collections::{BTreeMap, VecDeque};
+
+/// The market at the time Alice would take a decision.
+#[derive(Clone, Deserialize)]
+pub struct Snapshot {
+    pub ts: u64,
+    #[serde(default)]
+    pub pools: BTreeMap<Token, PublicPoolOverView>,
+    /// ICP amount in e8s paid for 1 token.
+    pub quotes: BTreeMap<Token, u64>,
+}
+
+/// Takes the decision of a step, given the market recorded so far.
+pub trait Strategy {
+    fn decide(
+        &mut self,
+        state: &State,
+        balances: &BTreeMap<Token, u64>,
+    ) -> Result<Decision, String>;
+}
+
+/// Buys a token after it dropped by `threshold` since the previous quote and
+/// sells it after it rose by as much.
+pub struct RuleBased {
+    pub threshold: f64,
+}
+
+impl Strategy for RuleBased {
+    fn decide(
+        &mut self,
+        state: &State,
+        balances: &BTreeMap<Token, u64>,
+    ) -> Result<Decision, String> {
+        let mut decision = (0.0, Decision::Hodl);
+        for token in [Token::Bob, Token::Alice] {
+            let last_return = match state.compute_token_returns(token).last() {
+                Some(last_return) => *last_return,
+                None => continue,
+            };
+            if last_return.abs() < self.threshold || last_return.abs() <= decision.0 {
+                continue;
+            }
+            if last_return < 0.0 {
+                decision = (last_return.abs(), Decision::Buy(token));
+            } else if balances.get(&token).copied().unwrap_or(0) > 0 {
+                decision = (last_return, Decision::Sell(token));
+            }
+        }
+        Ok(decision.1)
+    }
+}
+
+/// Plays back the answers of a model, one per step.
+pub struct Transcript {
+    pub answers: VecDeque<Result<Decision, String>>,
+}
+
+impl Transcript {
+    /// Parses raw answers as Alice does.
+    pub fn from_answers(answers: Vec<String>) -> Self {
+        Self {
+            answers: answers
+                .iter()
+                .map(|answer| parse_answer(answer).map(|answer| answer.decision))
+                .collect(),
+        }
+    }
+
+    /// Takes the first valid vote of each decision, as returned newest first
+    /// by `get_decisions`.
+    pub fn from_decisions(records: Vec<DecisionRecord>) -> Self {
+        Self {
+            answers: records
+                .iter()
+                .rev()
+                .map(|record| {
+                    record
+                        .votes
+                        .iter()
+                        .find_map(|vote| vote.answer.as_ref().ok())
+                        .map(|answer| answer.decision)
+                        .ok_or_else(|| "no valid vote".to_string())
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Strategy for Transcript {
+    fn decide(&mut self, _: &State, _: &BTreeMap<Token, u64>) -> Result<Decision, String> {
+        self.answers
+            .pop_front()
+            .unwrap_or_else(|| Err("the transcript has no more answers".to_string()))
+    }
+}
+
+pub struct BacktestConfig {
+    pub initial_balances: BTreeMap<Token, u64>,
+    /// Fee of the pool, in basis points of the traded amount.
+    pub fee_bps: u64,
+    /// Price impact of a trade, in basis points.
+    pub slippage_bps: u64,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            initial_balances: BTreeMap::from([(Token::Icp, 100 * 100_000_000)]),
+            fee_bps: 30,
+            slippage_bps: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestReport {
+    pub steps: usize,
+    pub trades: Vec<TradeAction>,
+    /// Decisions that could not be sized or filled.
+    pub rejected_trades: usize,
+    /// Steps where the strategy gave no decision.
+    pub failed_decisions: usize,
+    pub initial_value_e8s: u64,
+    pub final_value_e8s: u64,
+    /// Final value of the initial balances.
+    pub hodl_value_e8s: u64,
+    pub max_drawdown: f64,
+}
+
+impl BacktestReport {
+    pub fn total_return(&self) -> f64 {
+        self.final_value_e8s as f64 / self.initial_value_e8s as f64 - 1.0
+    }
+
+    pub fn hodl_return(&self) -> f64 {
+        self.hodl_value_e8s as f64 / self.initial_value_e8s as f64 - 1.0
+    }
+}
+
+/// The quote paid by a trade, once fees and slippage are accounted for.
+fn execution_quote(trade: &TradeAction, quote: u64, config: &BacktestConfig) -> u64 {
+    let cost_bps = (config.fee_bps + config.slippage_bps) as u128;
+    let quote = quote as u128;
+    let quote = match trade {
+        TradeAction::Buy { .. } => quote * (10_000 + cost_bps) / 10_000,
+        TradeAction::Sell { .. } => quote * 10_000u128.saturating_sub(cost_bps) / 10_000,
+    };
+    quote.min(u64::MAX as u128) as u64
+}
+
+/// Takes a decision at every snapshot once enough prices were recorded, as
+/// Alice does every `TAKE_DECISION_DELAY`.
+pub fn run(
+    snapshots: &[Snapshot],
+    strategy: &mut impl Strategy,
+    config: &BacktestConfig,
+) -> Result<BacktestReport, String> {
+    let first = snapshots.first().ok_or("no snapshots to replay")?;
+    let initial_value_e8s = value_in_icp(&config.initial_balances, &first.quotes)
+        .ok_or("the first snapshot lacks a quote of the initial balances")?;
+
+    let mut state = State::new();
+    let mut portfolio = PaperPortfolio::new(first.ts, config.initial_balances.clone());
+    let mut quotes = BTreeMap::new();
+    let mut report = BacktestReport {
+        steps: 0,
+        trades: vec![],
+        rejected_trades: 0,
+        failed_decisions: 0,
+        initial_value_e8s,
+        final_value_e8s: initial_value_e8s,
+        hodl_value_e8s: initial_value_e8s,
+        max_drawdown: 0.0,
+    };
+    let mut peak_value = initial_value_e8s;
+
+    for (step, snapshot) in snapshots.iter().enumerate() {
+        for (token, pool) in &snapshot.pools {
+            state.insert_price(*token, pool.clone());
+        }
+        for (token, value) in &snapshot.quotes {
+            let quote = Quote {
+                value: *value,
+                ts: snapshot.ts,
+            };
+            state.insert_quote(*token, quote);
+            quotes.insert(*token, *value);
+        }
+        state.suppress_quotes_older_than(snapshot.ts);
+        state.balances = portfolio.balances.clone();
+        report.steps += 1;
+
+        if step + 1 >= MIN_PRICE_HISTORY {
+            match strategy.decide(&state, &portfolio.balances) {
+                Ok(decision) => {
+                    match trade_action(&state, decision, None, &portfolio.balances, snapshot.ts) {
+                        None => {}
+                        Some(Ok(trade)) => {
+                            let filled = quotes.get(&trade.token()).map(|quote| {
+                                portfolio.apply(&trade, execution_quote(&trade, *quote, config))
+                            });
+                            match filled {
+                                Some(Ok(_)) => report.trades.push(trade),
+                                _ => report.rejected_trades += 1,
+                            }
+                        }
+                        Some(Err(_)) => report.rejected_trades += 1,
+                    }
+                }
+                Err(_) => report.failed_decisions += 1,
+            }
+        }
+
+        if let Some(value) = value_in_icp(&portfolio.balances, &quotes) {
+            report.final_value_e8s = value;
+            peak_value = peak_value.max(value);
+            let drawdown = 1.0 - value as f64 / peak_value as f64;
+            report.max_drawdown = report.max_drawdown.max(drawdown);
+        }
+        if let Some(value) = value_in_icp(&config.initial_balances, &quotes) {
+            report.hodl_value_e8s = value;
+        }
+    }
+    Ok(report)
+}
+
+/// Reads quotes from lines of `ts,bob,alice`, after a header line. An empty
+/// quote is a missing one.
+pub fn parse_csv(input: &str) -> Result<Vec<Snapshot>, String> {
+    input
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            let line_number = index + 2;
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() != 3 {
+                return Err(format!("line {line_number}: expected ts,bob,alice"));
+            }
+            let parse = |field: &str| {
+                field
+                    .parse::<u64>()
+                    .map_err(|e| format!("line {line_number}: {e}"))
+            };
+            let mut quotes = BTreeMap::new();
+            for (token, field) in [(Token::Bob, fields[1]), (Token::Alice, fields[2])] {
+                if !field.is_empty() {
+                    quotes.insert(token, parse(field)?);
+                }
+            }
+            Ok(Snapshot {
+                ts: parse(fields[0])?,
+                pools: BTreeMap::new(),
+                quotes,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_NANOS: u64 = 3_600_000_000_000;
+
+    fn snapshots(bob_quotes: &[u64]) -> Vec<Snapshot> {
+        bob_quotes
+            .iter()
+            .enumerate()
+            .map(|(i, quote)| Snapshot {
+                ts: i as u64 * 4 * HOUR_NANOS,
+                pools: BTreeMap::new(),
+                quotes: BTreeMap::from([(Token::Bob, *quote), (Token::Alice, 1_000_000)]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_parse_csv_quotes() {
+        let snapshots = parse_csv("ts,bob,alice\n0,50000000,\n4,51000000,1000\n").unwrap();
+
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(
+            snapshots[0].quotes,
+            BTreeMap::from([(Token::Bob, 50_000_000)])
+        );
+        assert_eq!(snapshots[1].quotes.get(&Token::Alice), Some(&1_000));
+        assert!(parse_csv("ts,bob,alice\n0,abc,\n").is_err());
+    }
+
+    #[test]
+    fn should_only_decide_with_enough_history() {
+        let mut strategy = Transcript::from_answers(vec!["HODL".to_string(); 2]);
+
+        let report = run(
+            &snapshots(&[50_000_000; 5]),
+            &mut strategy,
+            &BacktestConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.steps, 5);
+        assert_eq!(report.failed_decisions, 0);
+        assert!(report.trades.is_empty());
+        assert_eq!(report.final_value_e8s, report.initial_value_e8s);
+    }
+
+    #[test]
+    fn should_report_returns_and_drawdown() {
+        let mut strategy = Transcript::from_answers(vec![
+            r#"{"action": "BUY", "token": "BOB"}"#.to_string(),
+            "HODL".to_string(),
+            "HODL".to_string(),
+        ]);
+        let config = BacktestConfig {
+            initial_balances: BTreeMap::from([
+                (Token::Icp, 100 * 100_000_000),
+                (Token::Bob, 100 * 100_000_000),
+            ]),
+            ..Default::default()
+        };
+
+        let report = run(
+            &snapshots(&[
+                50_000_000, 50_000_000, 50_000_000, 50_000_000, 25_000_000, 75_000_000,
+            ]),
+            &mut strategy,
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(report.trades.len(), 1);
+        assert!(matches!(
+            report.trades[0],
+            TradeAction::Buy {
+                token: Token::Bob,
+                ..
+            }
+        ));
+        assert!(report.max_drawdown > 0.0);
+        assert!(report.total_return() > 0.0);
+        assert!(report.total_return() > report.hodl_return());
+    }
+}
@@ -0,0 +1,135 @@
+//! Replays recorded market data through Alice's trade sizing.
+//!
+//! ```text
+//! cargo run -p alice --features backtest --bin backtest -- <snapshots.json|quotes.csv> [options]
+//!
+//!   --transcript <file>   JSON array of raw model answers, or the decisions
+//!                         returned by `get_decisions`. Without a transcript,
+//!                         the rule-based strategy is used.
+//!   --threshold <ratio>   move triggering the rule-based strategy (0.05)
+//!   --fee-bps <bps>       pool fee (30)
+//!   --slippage-bps <bps>  price impact of a trade (50)
+//!   --icp <e8s>           initial ICP balance (100 ICP)
+//!   --bob <e8s>           initial BOB balance (0)
+//!   --alice <e8s>         initial ALICE balance (0)
+//! ```
+
+use alice::backtest::{
+    parse_csv, run, BacktestConfig, BacktestReport, RuleBased, Snapshot, Transcript,
+};
+use alice::{DecisionRecord, DisplayAmount, Token, TradeAction};
+use std::process::exit;
+
+struct Args {
+    snapshots: String,
+    transcript: Option<String>,
+    threshold: f64,
+    config: BacktestConfig,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut parsed = Args {
+        snapshots: String::new(),
+        transcript: None,
+        threshold: 0.05,
+        config: BacktestConfig::default(),
+    };
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            parsed.snapshots = arg;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {arg}"))?;
+        let invalid = |e: &dyn std::fmt::Display| format!("invalid {arg}: {e}");
+        match arg.as_str() {
+            "--transcript" => parsed.transcript = Some(value),
+            "--threshold" => parsed.threshold = value.parse().map_err(|e| invalid(&e))?,
+            "--fee-bps" => parsed.config.fee_bps = value.parse().map_err(|e| invalid(&e))?,
+            "--slippage-bps" => {
+                parsed.config.slippage_bps = value.parse().map_err(|e| invalid(&e))?
+            }
+            "--icp" | "--bob" | "--alice" => {
+                let token = match arg.as_str() {
+                    "--icp" => Token::Icp,
+                    "--bob" => Token::Bob,
+                    _ => Token::Alice,
+                };
+                let amount = value.parse().map_err(|e| invalid(&e))?;
+                parsed.config.initial_balances.insert(token, amount);
+            }
+            _ => return Err(format!("unknown option {arg}")),
+        }
+    }
+    if parsed.snapshots.is_empty() {
+        return Err("missing the snapshots file".to_string());
+    }
+    Ok(parsed)
+}
+
+fn read_snapshots(path: &str) -> Result<Vec<Snapshot>, String> {
+    let input = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    if path.ends_with(".csv") {
+        return parse_csv(&input);
+    }
+    serde_json::from_str(&input).map_err(|e| format!("{path}: {e}"))
+}
+
+fn read_transcript(path: &str) -> Result<Transcript, String> {
+    let input = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    if let Ok(answers) = serde_json::from_str::<Vec<String>>(&input) {
+        return Ok(Transcript::from_answers(answers));
+    }
+    serde_json::from_str::<Vec<DecisionRecord>>(&input)
+        .map(Transcript::from_decisions)
+        .map_err(|e| format!("{path}: {e}"))
+}
+
+fn print_report(report: &BacktestReport) {
+    let buys = report
+        .trades
+        .iter()
+        .filter(|trade| matches!(trade, TradeAction::Buy { .. }))
+        .count();
+    println!("steps:            {}", report.steps);
+    println!(
+        "trades:           {} ({buys} buys, {} sells)",
+        report.trades.len(),
+        report.trades.len() - buys
+    );
+    println!("rejected trades:  {}", report.rejected_trades);
+    println!("failed decisions: {}", report.failed_decisions);
+    println!(
+        "value:            {} ICP -> {} ICP",
+        DisplayAmount(report.initial_value_e8s),
+        DisplayAmount(report.final_value_e8s)
+    );
+    println!("return:           {:.2}%", 100.0 * report.total_return());
+    println!("hodl return:      {:.2}%", 100.0 * report.hodl_return());
+    println!("max drawdown:     {:.2}%", 100.0 * report.max_drawdown);
+}
+
+fn backtest(args: Args) -> Result<BacktestReport, String> {
+    let snapshots = read_snapshots(&args.snapshots)?;
+    match &args.transcript {
+        Some(path) => run(&snapshots, &mut read_transcript(path)?, &args.config),
+        None => {
+            let mut strategy = RuleBased {
+                threshold: args.threshold,
+            };
+            run(&snapshots, &mut strategy, &args.config)
+        }
+    }
+}
+
+fn main() {
+    match parse_args().and_then(backtest) {
+        Ok(report) => print_report(&report),
+        Err(e) => {
+            eprintln!("backtest: {e}");
+            exit(1);
+        }
+    }
+}
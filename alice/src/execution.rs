@@ -0,0 +1,180 @@
+use crate::{Action, Token, TradeAction};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use strum::IntoEnumIterator;
+
+/// What an action moved once executed, `amount` is the amount sent after the
+/// fees were deducted.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct StepOutcome {
+    pub amount: u64,
+    pub fee: u64,
+    pub amount_out: Option<u64>,
+}
+
+/// An attempt to execute an action of the trade.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct ExecutionStep {
+    pub ts: u64,
+    pub action: Action,
+    pub result: Result<StepOutcome, String>,
+}
+
+/// How a trade went, once its output was withdrawn. Prices are in ICP per
+/// token, a positive slippage is a price worse than the quote.
+#[derive(Debug, Clone, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    pub completed_at: u64,
+    pub requested: (Token, u64),
+    pub received: (Token, u64),
+    pub fees: BTreeMap<Token, u64>,
+    pub effective_price: Option<f64>,
+    pub quote_price: Option<f64>,
+    pub slippage: Option<f64>,
+}
+
+/// The lifecycle of a trade, identified by its index in the trade history.
+#[derive(Debug, Clone, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct TradeExecution {
+    pub trade_id: u64,
+    pub trade: TradeAction,
+    /// ICP amount in e8s paid for 1 token when the trade was decided.
+    pub quote: Option<u64>,
+    pub steps: Vec<ExecutionStep>,
+    pub report: Option<ExecutionReport>,
+}
+
+fn fee_token(action: &Action) -> Token {
+    match action {
+        Action::Icrc2Approve { token, .. } | Action::Withdraw { token, .. } => *token,
+        Action::Swap { from, .. } => *from,
+        Action::DepositFrom { ledger_id, .. } => Token::iter()
+            .find(|token| token.ledger_id() == *ledger_id)
+            .unwrap_or(Token::Icp),
+    }
+}
+
+impl TradeExecution {
+    pub fn new(trade_id: u64, trade: TradeAction, quote: Option<u64>) -> Self {
+        Self {
+            trade_id,
+            trade,
+            quote,
+            steps: vec![],
+            report: None,
+        }
+    }
+
+    /// Records an attempt, the trade completes when its output is withdrawn.
+    pub fn record(&mut self, ts: u64, action: Action, result: Result<StepOutcome, String>) {
+        let received = match (&action, &result) {
+            (Action::Withdraw { token, .. }, Ok(outcome)) => {
+                Some((*token, outcome.amount_out.unwrap_or(outcome.amount)))
+            }
+            _ => None,
+        };
+        self.steps.push(ExecutionStep { ts, action, result });
+        if let Some(received) = received {
+            self.report = Some(self.build_report(ts, received));
+        }
+    }
+
+    fn build_report(&self, completed_at: u64, received: (Token, u64)) -> ExecutionReport {
+        let (requested, is_buy) = match &self.trade {
+            TradeAction::Buy { amount, .. } => ((Token::Icp, *amount), true),
+            TradeAction::Sell { token, amount, .. } => ((*token, *amount), false),
+        };
+        let mut fees = BTreeMap::new();
+        for step in &self.steps {
+            if let Ok(outcome) = &step.result {
+                *fees.entry(fee_token(&step.action)).or_default() += outcome.fee;
+            }
+        }
+        let (icp, tokens) = if is_buy {
+            (requested.1, received.1)
+        } else {
+            (received.1, requested.1)
+        };
+        let effective_price = (tokens > 0).then(|| icp as f64 / tokens as f64);
+        let quote_price = self.quote.map(|quote| quote as f64 / 100_000_000.0);
+        let slippage = effective_price
+            .zip(quote_price)
+            .filter(|(_, quote_price)| *quote_price > 0.0)
+            .map(|(price, quote_price)| {
+                if is_buy {
+                    price / quote_price - 1.0
+                } else {
+                    1.0 - price / quote_price
+                }
+            });
+        ExecutionReport {
+            completed_at,
+            requested,
+            received,
+            fees,
+            effective_price,
+            quote_price,
+            slippage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(amount: u64, fee: u64, amount_out: Option<u64>) -> Result<StepOutcome, String> {
+        Ok(StepOutcome {
+            amount,
+            fee,
+            amount_out,
+        })
+    }
+
+    #[test]
+    fn should_report_a_completed_buy() {
+        let trade = TradeAction::Buy {
+            token: Token::Bob,
+            amount: 100_000_000,
+            ts: 0,
+            rationale: None,
+        };
+        let actions = trade.actions();
+        // 1 BOB = 0.5 ICP
+        let mut execution = TradeExecution::new(0, trade, Some(50_000_000));
+
+        execution.record(1, actions[0].clone(), outcome(100_000_000, 10_000, None));
+        execution.record(2, actions[1].clone(), Err("pool unavailable".to_string()));
+        execution.record(3, actions[1].clone(), outcome(99_980_000, 10_000, None));
+        execution.record(
+            4,
+            actions[2].clone(),
+            outcome(99_980_000, 0, Some(190_000_000)),
+        );
+        assert_eq!(execution.report, None);
+        let withdraw = Action::Withdraw {
+            pool_id: candid::Principal::anonymous(),
+            token: Token::Bob,
+            amount: 190_000_000,
+        };
+        execution.record(
+            5,
+            withdraw,
+            outcome(189_000_000, 1_000_000, Some(188_000_000)),
+        );
+
+        let report = execution.report.unwrap();
+        assert_eq!(execution.steps.len(), 5);
+        assert_eq!(report.completed_at, 5);
+        assert_eq!(report.requested, (Token::Icp, 100_000_000));
+        assert_eq!(report.received, (Token::Bob, 188_000_000));
+        assert_eq!(
+            report.fees,
+            BTreeMap::from([(Token::Icp, 20_000), (Token::Bob, 1_000_000)])
+        );
+        assert_eq!(report.quote_price, Some(0.5));
+        let slippage = report.slippage.unwrap();
+        assert!((slippage - (100.0 / 188.0 / 0.5 - 1.0)).abs() < 1e-9);
+    }
+}
@@ -1,4 +1,7 @@
+use crate::memory::get_role;
 use crate::{mutate_state, TaskType};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum TaskGuardError {
@@ -28,3 +31,49 @@ impl Drop for TaskGuard {
         });
     }
 }
+
+/// What a principal may do on Alice, each role includes the ones before it.
+/// Controllers have every role.
+#[derive(
+    Debug, CandidType, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Deserialize, Serialize,
+)]
+pub enum Role {
+    /// Reads the internal state of Alice.
+    Viewer,
+    /// Adjusts how decisions are taken.
+    Operator,
+    /// Manages the secrets, the system prompt and the settings of Alice.
+    Admin,
+}
+
+impl Role {
+    pub fn includes(&self, role: Role) -> bool {
+        *self >= role
+    }
+}
+
+/// Checks that the caller is a controller or was granted a role including
+/// `role`.
+pub fn ensure_role(role: Role) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    if ic_cdk::api::is_controller(&caller) {
+        return Ok(());
+    }
+    match get_role(&caller) {
+        Some(granted) if granted.includes(role) => Ok(()),
+        _ => Err(format!("{caller} does not have the {role:?} role")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_include_lower_roles() {
+        assert!(Role::Admin.includes(Role::Viewer));
+        assert!(Role::Operator.includes(Role::Operator));
+        assert!(!Role::Operator.includes(Role::Admin));
+        assert!(!Role::Viewer.includes(Role::Operator));
+    }
+}
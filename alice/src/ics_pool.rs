@@ -86,6 +86,41 @@ pub async fn deposit_from(pool_id: Principal, args: DepositArgs) -> Result<Nat,
     }
 }
 
+#[derive(CandidType, Deserialize)]
+pub struct UnusedBalance {
+    pub balance0: Nat,
+    pub balance1: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum UnusedBalanceResult {
+    #[serde(rename = "ok")]
+    Ok(UnusedBalance),
+    #[serde(rename = "err")]
+    Err(ICSError),
+}
+
+/// The balances deposited by `user` in the pool and not swapped.
+pub async fn get_user_unused_balance(
+    pool_id: Principal,
+    user: Principal,
+) -> Result<UnusedBalance, String> {
+    let result: Result<(UnusedBalanceResult,), (i32, String)> =
+        ic_cdk::api::call::call(pool_id, "getUserUnusedBalance", (user,))
+            .await
+            .map_err(|(code, msg)| (code as i32, msg));
+    match result {
+        Ok((res,)) => match res {
+            UnusedBalanceResult::Ok(balance) => Ok(balance),
+            UnusedBalanceResult::Err(e) => Err(format!("Error while calling canister {:?}", e)),
+        },
+        Err((code, msg)) => Err(format!(
+            "Error while calling canister ({}): {:?}",
+            code, msg
+        )),
+    }
+}
+
 #[derive(CandidType, Deserialize)]
 pub struct WithdrawArgs {
     pub amount: Nat,
@@ -1,35 +1,55 @@
 use crate::bob::refresh_miner_settings;
+use crate::execution::{StepOutcome, TradeExecution};
 use crate::guard::TaskGuard;
 use crate::ics_pool::{
-    deposit_from, get_pool, quote, swap, withdraw, DepositArgs, SwapArgs, WithdrawArgs,
+    deposit_from, get_pool, get_user_unused_balance, quote, swap, withdraw, DepositArgs, SwapArgs,
+    WithdrawArgs,
 };
 use crate::ledger::{approve, balance_of};
-use crate::llm::{Message, Prompt};
+use crate::llm::{
+    configured_providers, prompt_all, prompt_with_failover, quorum_answer, LlmError, Message,
+    RedactedProvider, Sample,
+};
 use crate::logs::{DEBUG, INFO};
 use crate::memory::{
-    get_context, next_action, pop_front_action, push_action, push_actions, push_trade_action,
+    get_actions, get_config, get_consensus_config, get_context, get_paper_portfolio,
+    insert_trade_execution, next_action, push_action, push_actions, push_decision_record,
+    push_trade_action, record_execution_step, remove_action, set_paper_portfolio, update_action,
 };
-use crate::state::{mutate_state, read_state, Quote};
+use crate::paper::PaperPortfolio;
+use crate::state::{mutate_state, read_state, Quote, State};
 use crate::tasks::{schedule_after, schedule_now, TaskType};
 use candid::{CandidType, Deserialize, Nat, Principal};
 use futures::future::join_all;
 use ic_canister_log::log;
 use ic_cdk::api::management_canister::main::raw_rand;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::time::Duration;
 use strum::{EnumIter, IntoEnumIterator};
 
+#[cfg(feature = "backtest")]
+pub mod backtest;
 pub mod bob;
+pub mod execution;
 pub mod guard;
 pub mod ics_pool;
 pub mod ledger;
 pub mod llm;
 pub mod logs;
 pub mod memory;
+pub mod paper;
+pub mod queue;
 pub mod state;
 pub mod tasks;
 
+pub use guard::Role;
+pub use llm::{parse_answer, parse_decision, ConsensusConfig, ModelAnswer};
+pub use queue::{
+    dead_letter_action, requeue_dead_letter, retry_delay, ActionError, DeadLetter, QueuedAction,
+};
+
 pub const ICP_LEDGER: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
 pub const BOB_LEDGER: &str = "7pail-xaaaa-aaaas-aabmq-cai";
 pub const ALICE_LEDGER: &str = "oj6if-riaaa-aaaaq-aaeha-cai";
@@ -45,6 +65,9 @@ pub const TAKE_DECISION_DELAY: Duration = Duration::from_secs(14_400);
 // 1 hour
 const FETCH_CONTEXT_DELAY: Duration = Duration::from_secs(3_600);
 
+// Number of prices of each pool Alice needs before deciding.
+pub const MIN_PRICE_HISTORY: usize = 4;
+
 #[derive(
     Debug, EnumIter, CandidType, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Deserialize, Serialize,
 )]
@@ -133,73 +156,91 @@ impl Token {
     }
 }
 
-pub fn parse_trade_action(input: &str) -> Result<TradeAction, String> {
-    let parts: Vec<&str> = input.split_whitespace().collect();
-    if parts.len() != 2 {
-        if parts.len() == 1 {
-            return Err(format!(
-                "No action taken: {}, not doing anything.",
-                parts[0]
-            ));
-        } else {
-            return Err("Invalid input format".to_string());
-        }
-    }
-
-    let action = parts[0].to_lowercase();
-    let token = match parts[1].to_lowercase().as_str() {
-        "icp" => Token::Icp,
-        "alice" => Token::Alice,
-        "bob" => Token::Bob,
-        _ => return Err("Unknown token".to_string()),
-    };
-
-    if token == Token::Icp {
-        return Err(format!("Cannot buy nor sell ICP",));
-    }
+/// The answer of the model, before it is sized into a trade.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+pub enum Decision {
+    Buy(Token),
+    Sell(Token),
+    Hodl,
+}
 
-    let amount_to_trade = match action.as_str() {
-        "buy" => read_state(|s| s.amount_to_buy(token)),
-        "sell" => read_state(|s| s.balances.get(&token).unwrap_or(&0).clone()) / 10,
-        _ => return Err("Unknown action".to_string()),
+/// Sizes the trade of the decision for a portfolio of `balances`, `None` when
+/// there is nothing to trade.
+pub fn trade_action(
+    state: &State,
+    decision: Decision,
+    rationale: Option<String>,
+    balances: &BTreeMap<Token, u64>,
+    ts: u64,
+) -> Option<Result<TradeAction, String>> {
+    let (token, amount_to_trade) = match decision {
+        Decision::Buy(token) => (token, state.amount_to_buy_with(balances, token)),
+        Decision::Sell(token) => (token, balances.get(&token).copied().unwrap_or(0) / 10),
+        Decision::Hodl => return None,
     };
 
     if amount_to_trade < token.minimum_amount_to_trade() {
-        return Err(format!(
+        return Some(Err(format!(
             "{token} balance too low, minimum to trade is {} got {}",
             DisplayAmount(token.minimum_amount_to_trade()),
             DisplayAmount(amount_to_trade)
-        ));
+        )));
     }
 
-    match action.as_str() {
-        "buy" => Ok(TradeAction::Buy {
+    Some(Ok(match decision {
+        Decision::Buy(_) => TradeAction::Buy {
             token,
             amount: amount_to_trade,
-            ts: timestamp_nanos(),
-        }),
-        "sell" => Ok(TradeAction::Sell {
+            ts,
+            rationale,
+        },
+        _ => TradeAction::Sell {
             token,
             amount: amount_to_trade,
-            ts: timestamp_nanos(),
-        }),
-        _ => Err("Unknown action".to_string()),
-    }
+            ts,
+            rationale,
+        },
+    }))
 }
 
 #[derive(Debug, Eq, PartialEq, CandidType, Serialize, Deserialize, Clone)]
 pub enum TradeAction {
-    Buy { token: Token, amount: u64, ts: u64 },
-    Sell { token: Token, amount: u64, ts: u64 },
+    Buy {
+        token: Token,
+        amount: u64,
+        ts: u64,
+        #[serde(default)]
+        rationale: Option<String>,
+    },
+    Sell {
+        token: Token,
+        amount: u64,
+        ts: u64,
+        #[serde(default)]
+        rationale: Option<String>,
+    },
 }
 
 impl TradeAction {
+    pub fn ts(&self) -> u64 {
+        match self {
+            TradeAction::Buy { ts, .. } | TradeAction::Sell { ts, .. } => *ts,
+        }
+    }
+
+    pub fn token(&self) -> Token {
+        match self {
+            TradeAction::Buy { token, .. } | TradeAction::Sell { token, .. } => *token,
+        }
+    }
+
     fn actions(&self) -> Vec<Action> {
         match self {
             TradeAction::Buy {
                 token,
                 amount,
                 ts: _,
+                rationale: _,
             } => {
                 vec![
                     Action::Icrc2Approve {
@@ -225,6 +266,7 @@ impl TradeAction {
                 token,
                 amount,
                 ts: _,
+                rationale: _,
             } => {
                 vec![
                     Action::Icrc2Approve {
@@ -255,6 +297,7 @@ impl TradeAction {
                 token,
                 amount: _,
                 ts: _,
+                rationale: _,
             } => match token {
                 Token::Icp => panic!(),
                 Token::Bob => false,
@@ -264,6 +307,7 @@ impl TradeAction {
                 token,
                 amount: _,
                 ts: _,
+                rationale: _,
             } => match token {
                 Token::Icp => panic!(),
                 Token::Bob => true,
@@ -299,28 +343,107 @@ pub enum Action {
     },
 }
 
+impl Action {
+    pub fn pool_id(&self) -> Principal {
+        match self {
+            Action::Icrc2Approve { pool_id, .. }
+            | Action::DepositFrom { pool_id, .. }
+            | Action::Swap { pool_id, .. }
+            | Action::Withdraw { pool_id, .. } => *pool_id,
+        }
+    }
+}
+
 pub async fn process_logic() -> Result<bool, String> {
-    if let Some(action) = next_action() {
-        return match execute_action(action.clone()).await {
-            Ok(()) => {
-                pop_front_action();
+    if let Some((id, mut queued)) = next_action() {
+        let result = execute_action(queued.trade_id, queued.action.clone()).await;
+        if let Some(trade_id) = queued.trade_id {
+            record_execution_step(
+                trade_id,
+                timestamp_nanos(),
+                queued.action.clone(),
+                result.clone(),
+            );
+        }
+        return match result {
+            Ok(_) => {
+                remove_action(id);
                 Ok(true)
             }
-            Err(e) => Err(e),
+            Err(e) => {
+                queued.record_failure(timestamp_nanos(), e.clone());
+                let attempts = queued.attempts;
+                update_action(id, queued);
+                if attempts >= get_config().max_action_attempts {
+                    // The action may have been skipped meanwhile.
+                    let _ = dead_letter_action(id, format!("failed {attempts} times: {e}"));
+                    return Ok(true);
+                }
+                Err(e)
+            }
         };
     }
 
     Ok(false)
 }
 
-async fn execute_action(action: Action) -> Result<(), String> {
+/// Withdraws the balances left in the pools by failed trades. Pools with
+/// queued actions are left alone as their balances are in use.
+pub async fn reconcile() -> Result<(), String> {
+    for token in [Token::Bob, Token::Alice] {
+        let pool_id = token.pool_id();
+        let pool_in_use = || {
+            get_actions()
+                .iter()
+                .any(|(_, queued)| queued.action.pool_id() == pool_id)
+        };
+        if pool_in_use() {
+            continue;
+        }
+        let unused = get_user_unused_balance(pool_id, ic_cdk::id()).await?;
+        if pool_in_use() {
+            continue;
+        }
+        // Both pools trade the token as token0 against ICP.
+        for (token, balance) in [(token, unused.balance0), (Token::Icp, unused.balance1)] {
+            let balance = nat_to_u64(balance).unwrap_or(0);
+            if balance > 2 * token.fee_e8s() {
+                log!(
+                    INFO,
+                    "[Reconcile] Withdrawing {} unused {token} from {pool_id}",
+                    DisplayAmount(balance)
+                );
+                push_action(QueuedAction::new(
+                    None,
+                    Action::Withdraw {
+                        pool_id,
+                        token,
+                        amount: balance,
+                    },
+                ));
+                schedule_now(TaskType::ProcessLogic);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn nat_to_u64(amount: Nat) -> Option<u64> {
+    amount.0.try_into().ok()
+}
+
+async fn execute_action(trade_id: Option<u64>, action: Action) -> Result<StepOutcome, String> {
     match action {
         Action::Icrc2Approve {
             pool_id,
             amount,
             token,
         } => match approve(pool_id, Nat::from(amount), token.ledger_id()).await {
-            Ok(_) => Ok(()),
+            Ok(_) => Ok(StepOutcome {
+                amount,
+                fee: token.fee_e8s(),
+                amount_out: None,
+            }),
             Err(e) => Err(format!("{e}")),
         },
         Action::DepositFrom {
@@ -328,8 +451,10 @@ async fn execute_action(action: Action) -> Result<(), String> {
             ledger_id,
             amount,
         } => {
-            let fee = ledger_to_fee_e8s(ledger_id).unwrap();
-            let amount = amount.checked_sub(2 * fee).unwrap();
+            let fee = ledger_to_fee_e8s(ledger_id).ok_or(format!("unknown ledger {ledger_id}"))?;
+            let amount = amount
+                .checked_sub(2 * fee)
+                .ok_or(format!("cannot deposit {amount}, lower than the fees"))?;
             match deposit_from(
                 pool_id,
                 DepositArgs {
@@ -340,7 +465,11 @@ async fn execute_action(action: Action) -> Result<(), String> {
             )
             .await
             {
-                Ok(_) => Ok(()),
+                Ok(deposited) => Ok(StepOutcome {
+                    amount,
+                    fee,
+                    amount_out: nat_to_u64(deposited),
+                }),
                 Err(e) => Err(format!("{e}")),
             }
         }
@@ -351,8 +480,10 @@ async fn execute_action(action: Action) -> Result<(), String> {
             amount,
             zero_for_one,
         } => {
-            let amount = amount.checked_sub(2 * from.fee_e8s()).unwrap();
-            let amount_out: u64 = quote(
+            let amount = amount
+                .checked_sub(2 * from.fee_e8s())
+                .ok_or(format!("cannot swap {amount}, lower than the fees"))?;
+            let amount_out = quote(
                 pool_id,
                 SwapArgs {
                     amount_in: format!("{amount}"),
@@ -360,10 +491,9 @@ async fn execute_action(action: Action) -> Result<(), String> {
                     amount_out_minimum: "0".to_string(),
                 },
             )
-            .await?
-            .0
-            .try_into()
-            .unwrap();
+            .await?;
+            let amount_out =
+                nat_to_u64(amount_out).ok_or("the quote does not fit in 64 bits".to_string())?;
             let amount_out = amount_out.checked_sub(amount_out / 10).unwrap();
             match swap(
                 pool_id,
@@ -376,13 +506,21 @@ async fn execute_action(action: Action) -> Result<(), String> {
             .await
             {
                 Ok(out_amount) => {
-                    let out_amount: u64 = out_amount.0.try_into().unwrap();
-                    push_action(Action::Withdraw {
-                        pool_id,
-                        token: to,
-                        amount: out_amount,
-                    });
-                    Ok(())
+                    let out_amount = nat_to_u64(out_amount)
+                        .ok_or("the swapped amount does not fit in 64 bits".to_string())?;
+                    push_action(QueuedAction::new(
+                        trade_id,
+                        Action::Withdraw {
+                            pool_id,
+                            token: to,
+                            amount: out_amount,
+                        },
+                    ));
+                    Ok(StepOutcome {
+                        amount,
+                        fee: 0,
+                        amount_out: Some(out_amount),
+                    })
                 }
                 Err(e) => Err(format!("{e}")),
             }
@@ -392,7 +530,10 @@ async fn execute_action(action: Action) -> Result<(), String> {
             token,
             amount,
         } => {
-            let amount = amount.checked_sub(token.fee_e8s()).unwrap();
+            let amount = amount
+                .checked_sub(token.fee_e8s())
+                .filter(|amount| *amount > 0)
+                .ok_or(format!("cannot withdraw {amount}, not above the fee"))?;
             match withdraw(
                 pool_id,
                 WithdrawArgs {
@@ -403,9 +544,13 @@ async fn execute_action(action: Action) -> Result<(), String> {
             )
             .await
             {
-                Ok(_) => {
+                Ok(withdrawn) => {
                     schedule_now(TaskType::RefreshContext);
-                    Ok(())
+                    Ok(StepOutcome {
+                        amount,
+                        fee: token.fee_e8s(),
+                        amount_out: nat_to_u64(withdrawn),
+                    })
                 }
                 Err(e) => Err(format!("{e}")),
             }
@@ -448,12 +593,24 @@ pub async fn refresh_prices() {
     join_all(futures).await;
 }
 
+/// The balances Alice takes decisions for: the paper portfolio when paper
+/// trading, the balances of Alice otherwise.
+pub fn trading_balances() -> BTreeMap<Token, u64> {
+    if get_config().paper_trading {
+        if let Some(portfolio) = get_paper_portfolio() {
+            return portfolio.balances;
+        }
+    }
+    read_state(|s| s.balances.clone())
+}
+
 fn build_portfolio() -> String {
+    let balances = trading_balances();
     read_state(|s| {
         let mut result = String::new();
 
         for token in Token::iter() {
-            if let Some(balance) = s.balances.get(&token) {
+            if let Some(balance) = balances.get(&token) {
                 result.push_str(&format!("- {} {}", DisplayAmount(*balance), token));
                 if token == Token::Icp {
                     if let Some(price) = s.prices.get(&Token::Bob).unwrap().get_latest() {
@@ -485,8 +642,9 @@ fn build_portfolio() -> String {
 pub fn build_user_prompt() -> String {
     format!(
         "Your portfolio is: \n{}
-        You can *only* answer with one of the following: BUY BOB, SELL BOB, BUY ALICE, HODL.
+        You can *only* decide one of the following: BUY BOB, SELL BOB, BUY ALICE, HODL.
         What should you do next to maximize shareholder value? 
+        Answer with a JSON object {{\"action\": \"BUY\" | \"SELL\" | \"HODL\", \"token\": \"BOB\" | \"ALICE\" | null, \"confidence\": <between 0 and 1>, \"rationale\": <one or two sentences>}}.
         ------- More Context
         The current evolution of each asset in your portfolio is the following, each entry is recorded every 4 hours: \n{}",
         build_portfolio(),
@@ -494,69 +652,247 @@ pub fn build_user_prompt() -> String {
     )
 }
 
-fn get_grok_prompt(user_prompt: String, seed: i32) -> Prompt {
-    Prompt {
-        messages: vec![
-            Message {
-                role: "system".to_string(),
-                content: get_context().unwrap(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: user_prompt,
-            },
-        ],
-        model: "grok-beta".to_string(),
-        stream: false,
-        temperature: 0,
-        seed,
-        top_logprobs: 0,
-        top_p: 0,
+#[derive(Debug, Clone, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct Vote {
+    pub provider: String,
+    #[serde(default)]
+    pub model: String,
+    pub seed: i32,
+    #[serde(default)]
+    pub raw_response: Option<String>,
+    pub answer: Result<ModelAnswer, LlmError>,
+}
+
+impl From<Sample<ModelAnswer>> for Vote {
+    fn from(sample: Sample<ModelAnswer>) -> Self {
+        Self {
+            provider: sample.provider,
+            model: sample.model,
+            seed: sample.seed,
+            raw_response: sample.raw_response,
+            answer: sample.answer,
+        }
     }
 }
 
-fn get_deepseek_prompt(user_prompt: String, seed: i32) -> Prompt {
-    Prompt {
-        messages: vec![
-            Message {
-                role: "system".to_string(),
-                content: get_context().unwrap(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: user_prompt,
-            },
-        ],
-        model: "deepseek-chat".to_string(),
-        stream: false,
-        temperature: 0,
-        seed,
-        top_logprobs: 0,
-        top_p: 0,
+#[derive(Debug, Clone, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub ts: u64,
+    #[serde(default)]
+    pub user_prompt: String,
+    pub quorum: u64,
+    pub votes: Vec<Vote>,
+    pub result: Result<TradeAction, String>,
+    /// The actions queued to execute the trade.
+    #[serde(default)]
+    pub actions: Vec<Action>,
+    /// Whether the trade was only simulated in the paper portfolio.
+    #[serde(default)]
+    pub paper_trading: bool,
+}
+
+/// Settings of Alice updated by the controllers.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Number of latest trades, along with their decisions, withheld from
+    /// the public history.
+    pub withheld_trades: u64,
+    /// Simulates the trades in the paper portfolio instead of swapping.
+    pub paper_trading: bool,
+    /// Failed attempts after which a queued action is dead-lettered.
+    pub max_action_attempts: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            withheld_trades: 1,
+            paper_trading: false,
+            max_action_attempts: 10,
+        }
+    }
+}
+
+/// A version of the system prompt given to the models.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct SystemPrompt {
+    pub version: u64,
+    pub ts: u64,
+    pub author: Principal,
+    pub content: String,
+}
+
+/// A system prompt version, without its content.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Deserialize)]
+pub struct SystemPromptInfo {
+    pub version: u64,
+    pub ts: u64,
+    pub author: Principal,
+    pub length: u64,
+}
+
+impl From<&SystemPrompt> for SystemPromptInfo {
+    fn from(prompt: &SystemPrompt) -> Self {
+        Self {
+            version: prompt.version,
+            ts: prompt.ts,
+            author: prompt.author,
+            length: prompt.content.len() as u64,
+        }
+    }
+}
+
+/// What Alice prompts the models with, without the secrets.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Deserialize)]
+pub struct LlmSettings {
+    pub providers: Vec<RedactedProvider>,
+    pub active_prompt_version: Option<u64>,
+    pub prompt_versions: Vec<SystemPromptInfo>,
+}
+
+async fn collect_votes(messages: &[Message], seed: i32) -> (Vec<Vote>, u64) {
+    let providers = configured_providers();
+    match get_consensus_config() {
+        Some(consensus) => {
+            let seeds: Vec<i32> = (0..consensus.samples_per_provider)
+                .map(|i| seed.wrapping_add(i as i32))
+                .collect();
+            let votes = prompt_all(&providers, messages, &seeds, parse_answer).await;
+            (
+                votes.into_iter().map(Vote::from).collect(),
+                consensus.quorum,
+            )
+        }
+        None => {
+            let votes = prompt_with_failover(&providers, messages, seed, parse_answer).await;
+            (votes.into_iter().map(Vote::from).collect(), 1)
+        }
+    }
+}
+
+fn decide(
+    votes: &[Vote],
+    quorum: u64,
+    balances: &BTreeMap<Token, u64>,
+) -> Result<TradeAction, String> {
+    let answers: Vec<&ModelAnswer> = votes
+        .iter()
+        .filter_map(|vote| vote.answer.as_ref().ok())
+        .collect();
+    if answers.is_empty() {
+        return Err("No LLM provider gave a valid answer".to_string());
+    }
+    let decisions: Vec<Decision> = answers.iter().map(|answer| answer.decision).collect();
+    let decision = quorum_answer(&decisions, quorum as usize).ok_or_else(|| {
+        format!(
+            "No quorum of {quorum} among {} votes, not doing anything.",
+            votes.len()
+        )
+    })?;
+    // Keep the rationale of the first model that took the decision.
+    let rationale = answers
+        .iter()
+        .find(|answer| answer.decision == decision)
+        .and_then(|answer| answer.rationale.clone());
+    read_state(|s| trade_action(s, decision, rationale, balances, timestamp_nanos()))
+        .unwrap_or_else(|| {
+            Err(format!(
+                "No action taken: {decision:?}, not doing anything."
+            ))
+        })
+}
+
+/// The paper portfolio to trade in, if paper trading is on.
+fn current_paper_portfolio() -> Result<Option<PaperPortfolio>, String> {
+    if get_config().paper_trading {
+        get_paper_portfolio()
+            .map(Some)
+            .ok_or("Paper trading without a paper portfolio".to_string())
+    } else {
+        Ok(None)
     }
 }
 
 pub async fn take_decision() -> Result<TradeAction, String> {
-    if read_state(|s| s.prices.get(&Token::Alice).unwrap().get_prices().len() < 4) {
+    if read_state(|s| s.prices.get(&Token::Alice).unwrap().get_prices().len() < MIN_PRICE_HISTORY) {
         return Err("Not yet ready to make a decision, not enough price history".to_string());
     }
+    current_paper_portfolio()?;
     if let Ok((random_array,)) = raw_rand().await {
         let seed = i32::from_le_bytes(random_array[..4].try_into().unwrap()) % i32::MAX;
         let seed = if seed < 0 { -seed } else { seed };
-        let prompt = build_user_prompt();
-
-        match crate::llm::prompt_xai(get_grok_prompt(prompt, seed)).await {
-            Ok(result) => match parse_trade_action(&result.choices[0].message.content) {
-                Ok(action) => {
-                    push_trade_action(action.clone());
-                    push_actions(action.actions());
-                    schedule_now(TaskType::ProcessLogic);
-                    Ok(action)
-                }
-                Err(e) => Err(e),
+        let user_prompt = build_user_prompt();
+        let messages = vec![
+            Message {
+                role: "system".to_string(),
+                content: get_context().ok_or("No context set, cannot take a decision")?,
             },
-            Err(e) => Err(e.to_string()),
+            Message {
+                role: "user".to_string(),
+                content: user_prompt.clone(),
+            },
+        ];
+
+        let (votes, quorum) = collect_votes(&messages, seed).await;
+        for vote in &votes {
+            log!(
+                INFO,
+                "[TakeDecision] {} (seed {}) voted {:?}",
+                vote.provider,
+                vote.seed,
+                vote.answer
+            );
         }
+        // The portfolio may have been traded or reset during the awaits.
+        let paper_portfolio = current_paper_portfolio()?;
+        let balances = match &paper_portfolio {
+            Some(portfolio) => portfolio.balances.clone(),
+            None => read_state(|s| s.balances.clone()),
+        };
+        let result = decide(&votes, quorum, &balances);
+        let actions = match (&result, &paper_portfolio) {
+            (Ok(action), None) => action.actions(),
+            _ => vec![],
+        };
+        push_decision_record(DecisionRecord {
+            ts: timestamp_nanos(),
+            user_prompt,
+            quorum,
+            votes,
+            result: result.clone(),
+            actions: actions.clone(),
+            paper_trading: paper_portfolio.is_some(),
+        });
+
+        let action = result?;
+        match paper_portfolio {
+            Some(mut portfolio) => {
+                let token = action.token();
+                let quote = read_state(|s| s.maybe_get_last_quote(token))
+                    .ok_or(format!("No quote for {token}, cannot paper trade"))?;
+                let paper_trade = portfolio.apply(&action, quote.value)?;
+                log!(INFO, "[TakeDecision] Paper traded: {:?}", paper_trade);
+                set_paper_portfolio(Some(portfolio));
+            }
+            None => {
+                let trade_id = push_trade_action(action.clone());
+                let quote = read_state(|s| s.maybe_get_last_quote(action.token()));
+                insert_trade_execution(TradeExecution::new(
+                    trade_id,
+                    action.clone(),
+                    quote.map(|quote| quote.value),
+                ));
+                push_actions(
+                    actions
+                        .into_iter()
+                        .map(|action| QueuedAction::new(Some(trade_id), action))
+                        .collect(),
+                );
+                schedule_now(TaskType::ProcessLogic);
+            }
+        }
+        Ok(action)
     } else {
         Err("Failed to generate random seed".to_string())
     }
@@ -582,6 +918,7 @@ pub async fn fetch_quotes() {
                         token,
                         amount: 0,
                         ts: 0,
+                        rationale: None,
                     }
                     .get_zero_for_one(),
                     amount_out_minimum: format!(""),
@@ -698,7 +1035,8 @@ pub fn timer() {
                         }
                         Err(e) => {
                             log!(INFO, "[ProcessLogic] Failed to process logic: {e}");
-                            schedule_after(Duration::from_secs(5), TaskType::ProcessLogic);
+                            let attempts = next_action().map_or(0, |(_, queued)| queued.attempts);
+                            schedule_after(retry_delay(attempts), TaskType::ProcessLogic);
                         }
                     }
 
@@ -736,6 +1074,54 @@ pub fn timer() {
                     );
                 });
             }
+            TaskType::Reconcile => {
+                ic_cdk::spawn(async move {
+                    let _guard = match TaskGuard::new(task_type) {
+                        Ok(guard) => guard,
+                        Err(_) => return,
+                    };
+
+                    if let Err(e) = reconcile().await {
+                        log!(INFO, "[Reconcile] Failed to reconcile the pools: {e}");
+                    }
+                    schedule_after(Duration::from_secs(24 * 60 * 60), TaskType::Reconcile);
+                });
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_dead_letter_a_withdrawal_not_above_the_fee() {
+        crate::memory::set_config(Config {
+            max_action_attempts: 2,
+            ..Default::default()
+        });
+        let withdraw = Action::Withdraw {
+            pool_id: Principal::anonymous(),
+            token: Token::Bob,
+            amount: Token::Bob.fee_e8s(),
+        };
+        push_action(QueuedAction::new(None, withdraw.clone()));
+
+        assert_eq!(
+            futures::executor::block_on(process_logic()),
+            Err(format!(
+                "cannot withdraw {}, not above the fee",
+                Token::Bob.fee_e8s()
+            ))
+        );
+        assert_eq!(get_actions()[0].1.attempts, 1);
+        assert_eq!(futures::executor::block_on(process_logic()), Ok(true));
+
+        assert!(get_actions().is_empty());
+        let dead_letters = crate::memory::get_dead_letters();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].1.queued.action, withdraw);
+        assert_eq!(dead_letters[0].1.queued.attempts, 2);
+    }
+}
@@ -1,6 +1,8 @@
-use crate::memory::get_api_key;
+use crate::memory::{get_api_key, get_llm_providers, set_api_key, set_llm_providers};
+use crate::{Decision, Token};
 use candid::CandidType;
-use ic_cdk::api::call::{call_with_payment128, CallResult};
+use futures::future::join_all;
+use ic_cdk::api::call::call_with_payment128;
 use ic_cdk::api::management_canister::http_request::{
     CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformContext,
 };
@@ -8,10 +10,13 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-const XAI_URL: &str = "https://api.x.ai/v1/chat/completions";
-const DEEPSEEK_URL: &str = "https://api.deepseek.com/chat/completions";
+// Every sample is an HTTPS outcall, keep their number bounded.
+const MAX_SAMPLES_PER_PROVIDER: u64 = 5;
 
-#[derive(Eq, PartialEq, Debug, CandidType, Serialize, Deserialize)]
+pub const XAI_URL: &str = "https://api.x.ai/v1/chat/completions";
+pub const DEEPSEEK_URL: &str = "https://api.deepseek.com/chat/completions";
+
+#[derive(Clone, Eq, PartialEq, Debug, CandidType, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Message {
     pub role: String,
@@ -28,6 +33,23 @@ pub struct Prompt {
     pub seed: i32,
     pub top_logprobs: u64,
     pub top_p: u64,
+    #[serde(rename = "response_format", skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+}
+
+/// Asks OpenAI-compatible providers to answer with a JSON object.
+#[derive(Eq, PartialEq, Debug, CandidType, Serialize, Deserialize)]
+pub struct ResponseFormat {
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl ResponseFormat {
+    pub fn json_object() -> Self {
+        Self {
+            kind: "json_object".to_string(),
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Debug, CandidType, Serialize, Deserialize)]
@@ -91,7 +113,7 @@ pub async fn http_call<I: Serialize, O: DeserializeOwned>(
     api_key: String,
     endpoint: String,
     payload: I,
-) -> CallResult<Result<O, Error>> {
+) -> Result<O, LlmError> {
     const KIB: u64 = 1024;
     let payload = serde_json::to_string(&payload).unwrap();
     let request = CanisterHttpRequestArgument {
@@ -130,58 +152,633 @@ pub async fn http_call<I: Serialize, O: DeserializeOwned>(
         (request,),
         cycles,
     )
-    .await?;
-
-    Ok(if response.status < 300u64 {
-        let result: O = serde_json::from_slice(&response.body).unwrap_or_else(|e| {
-            panic!(
-                "failed to decode response {}: {}",
-                String::from_utf8_lossy(&response.body),
-                e
-            )
-        });
-        Ok(result)
-    } else {
-        let e: Error = serde_json::from_slice(&response.body).unwrap_or_else(|e| {
-            panic!(
-                "failed to decode error {}: {}",
-                String::from_utf8_lossy(&response.body),
-                e
-            )
+    .await
+    .map_err(|(code, message)| LlmError::Rejected {
+        code: code as i32,
+        message,
+    })?;
+
+    if response.status >= 300u64 {
+        return Err(LlmError::HttpStatus {
+            code: response.status.0.try_into().unwrap_or(u16::MAX),
+            body_excerpt: body_excerpt(&response.body),
         });
-        Err(e)
-    })
+    }
+    serde_json::from_slice(&response.body).map_err(|e| LlmError::Decode(e.to_string()))
 }
 
-#[derive(Debug, Deserialize, Serialize, CandidType)]
-pub struct Error {
-    pub status: u16,
-    pub error: Option<String>,
-    pub message: String,
+fn body_excerpt(body: &[u8]) -> String {
+    const MAX_EXCERPT_CHARS: usize = 256;
+    String::from_utf8_lossy(body)
+        .chars()
+        .take(MAX_EXCERPT_CHARS)
+        .collect()
 }
 
-impl fmt::Display for Error {
+#[derive(Clone, Debug, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+pub enum LlmError {
+    /// The provider has no API key configured.
+    NoApiKey,
+    /// The HTTPS outcall was rejected by the system.
+    Rejected { code: i32, message: String },
+    /// The provider answered with an error status.
+    HttpStatus { code: u16, body_excerpt: String },
+    /// The response of the provider is not a chat completion.
+    Decode(String),
+    /// The chat completion has no choice.
+    EmptyChoices,
+    /// The answer does not follow the expected format.
+    InvalidAnswer { answer: String, reason: String },
+}
+
+impl fmt::Display for LlmError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match &self.error {
-            Some(kind) => write!(f, "{}: {}", kind, self.message),
-            None => write!(f, "{}", self.message),
+        match self {
+            LlmError::NoApiKey => write!(f, "no API key configured"),
+            LlmError::Rejected { code, message } => {
+                write!(f, "HTTPS outcall rejected ({code}): {message}")
+            }
+            LlmError::HttpStatus { code, body_excerpt } => {
+                write!(f, "HTTP status {code}: {body_excerpt}")
+            }
+            LlmError::Decode(e) => write!(f, "failed to decode the response: {e}"),
+            LlmError::EmptyChoices => write!(f, "the response has no choice"),
+            LlmError::InvalidAnswer { answer, reason } => {
+                write!(f, "invalid answer {answer:?}: {reason}")
+            }
+        }
+    }
+}
+
+/// A chat completion model Alice can ask for a decision.
+// Canister futures never move across threads, so the missing `Send` bound on
+// the returned futures does not matter.
+#[allow(async_fn_in_trait)]
+pub trait LlmProvider {
+    fn name(&self) -> String;
+
+    fn model(&self) -> String;
+
+    /// Returns the content of the answer to the conversation.
+    async fn complete(&self, messages: &[Message], seed: i32) -> Result<String, LlmError>;
+}
+
+/// An OpenAI-compatible chat completion endpoint, such as xAI or DeepSeek.
+/// The `api_key` is sent as is in the `Authorization` header, including the
+/// `Bearer` prefix.
+#[derive(Clone, Eq, PartialEq, Debug, CandidType, Serialize, Deserialize)]
+pub struct LlmProviderConfig {
+    pub name: String,
+    pub url: String,
+    pub model: String,
+    pub api_key: String,
+    /// Whether the provider supports the JSON `response_format`.
+    #[serde(default)]
+    pub json_mode: bool,
+}
+
+impl LlmProviderConfig {
+    pub fn xai(api_key: String) -> Self {
+        Self {
+            name: "xai".to_string(),
+            url: XAI_URL.to_string(),
+            model: "grok-beta".to_string(),
+            api_key,
+            json_mode: false,
+        }
+    }
+
+    pub fn deepseek(api_key: String) -> Self {
+        Self {
+            name: "deepseek".to_string(),
+            url: DEEPSEEK_URL.to_string(),
+            model: "deepseek-chat".to_string(),
+            api_key,
+            json_mode: true,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("provider name cannot be empty".to_string());
+        }
+        if !self.url.starts_with("https://") {
+            return Err(format!("provider {} must use an https url", self.name));
+        }
+        if self.model.is_empty() {
+            return Err(format!("provider {} has no model", self.name));
+        }
+        Ok(())
+    }
+}
+
+impl LlmProvider for LlmProviderConfig {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn model(&self) -> String {
+        self.model.clone()
+    }
+
+    async fn complete(&self, messages: &[Message], seed: i32) -> Result<String, LlmError> {
+        if self.api_key.is_empty() {
+            return Err(LlmError::NoApiKey);
+        }
+        let prompt = Prompt {
+            messages: messages.to_vec(),
+            model: self.model.clone(),
+            stream: false,
+            temperature: 0,
+            seed,
+            top_logprobs: 0,
+            top_p: 0,
+            response_format: self.json_mode.then(ResponseFormat::json_object),
+        };
+        let response: PromptResponse = http_call(
+            HttpMethod::POST,
+            self.api_key.clone(),
+            self.url.clone(),
+            prompt,
+        )
+        .await?;
+        response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .ok_or(LlmError::EmptyChoices)
+    }
+}
+
+/// Providers in priority order. Until providers are configured, Alice keeps
+/// prompting xAI with the key set through `set_api_key`.
+pub fn configured_providers() -> Vec<LlmProviderConfig> {
+    let providers = get_llm_providers();
+    if providers.is_empty() {
+        return vec![LlmProviderConfig::xai(get_api_key().unwrap_or_default())];
+    }
+    providers
+}
+
+/// Replaces the API key of the named provider. The key of the default xAI
+/// provider is rotated while no providers are configured.
+pub fn rotate_api_key(provider: &str, api_key: String) -> Result<(), String> {
+    let mut providers = get_llm_providers();
+    if providers.is_empty() && provider == LlmProviderConfig::xai(String::new()).name {
+        set_api_key(api_key);
+        return Ok(());
+    }
+    let config = providers
+        .iter_mut()
+        .find(|config| config.name == provider)
+        .ok_or_else(|| format!("unknown provider {provider}"))?;
+    config.api_key = api_key;
+    set_llm_providers(providers);
+    Ok(())
+}
+
+/// A provider as configured, without its API key.
+#[derive(Clone, Eq, PartialEq, Debug, CandidType, Deserialize)]
+pub struct RedactedProvider {
+    pub name: String,
+    pub url: String,
+    pub model: String,
+    pub json_mode: bool,
+    /// The last characters of the key, `None` if no key is set.
+    pub api_key: Option<String>,
+}
+
+impl From<&LlmProviderConfig> for RedactedProvider {
+    fn from(config: &LlmProviderConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            url: config.url.clone(),
+            model: config.model.clone(),
+            json_mode: config.json_mode,
+            api_key: redact_key(&config.api_key),
+        }
+    }
+}
+
+/// Only keeps the last 4 characters of keys long enough for them not to
+/// give the key away.
+fn redact_key(api_key: &str) -> Option<String> {
+    const VISIBLE_CHARS: usize = 4;
+    if api_key.is_empty() {
+        return None;
+    }
+    let chars: Vec<char> = api_key.chars().collect();
+    if chars.len() < 4 * VISIBLE_CHARS {
+        return Some("****".to_string());
+    }
+    let visible: String = chars[chars.len() - VISIBLE_CHARS..].iter().collect();
+    Some(format!("****{visible}"))
+}
+
+/// One answer of a provider, kept whole for the decision audit log.
+#[derive(Debug, PartialEq)]
+pub struct Sample<T> {
+    pub provider: String,
+    pub model: String,
+    pub seed: i32,
+    /// The content of the answer, if the provider answered.
+    pub raw_response: Option<String>,
+    pub answer: Result<T, LlmError>,
+}
+
+async fn sample<P: LlmProvider, T>(
+    provider: &P,
+    messages: &[Message],
+    seed: i32,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Sample<T> {
+    let (raw_response, answer) = match provider.complete(messages, seed).await {
+        Ok(content) => {
+            let answer = parse(&content).map_err(|reason| LlmError::InvalidAnswer {
+                answer: content.clone(),
+                reason,
+            });
+            (Some(content), answer)
+        }
+        Err(e) => (None, Err(e)),
+    };
+    Sample {
+        provider: provider.name(),
+        model: provider.model(),
+        seed,
+        raw_response,
+        answer,
+    }
+}
+
+/// Asks the providers in order until one gives an answer that `parse`
+/// accepts. Returns every answer, only the last one can be valid.
+pub async fn prompt_with_failover<P: LlmProvider, T>(
+    providers: &[P],
+    messages: &[Message],
+    seed: i32,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Vec<Sample<T>> {
+    let mut samples = vec![];
+    for provider in providers {
+        let sample = sample(provider, messages, seed, &parse).await;
+        let answered = sample.answer.is_ok();
+        samples.push(sample);
+        if answered {
+            break;
+        }
+    }
+    samples
+}
+
+/// Asks every provider once per seed, concurrently.
+pub async fn prompt_all<P: LlmProvider, T>(
+    providers: &[P],
+    messages: &[Message],
+    seeds: &[i32],
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Vec<Sample<T>> {
+    let parse = &parse;
+    let prompts = providers.iter().flat_map(|provider| {
+        seeds
+            .iter()
+            .map(move |&seed| sample(provider, messages, seed, parse))
+    });
+    join_all(prompts).await
+}
+
+/// The answer given at least `quorum` times, unless another answer was given
+/// as often.
+pub fn quorum_answer<T: PartialEq + Clone>(answers: &[T], quorum: usize) -> Option<T> {
+    let mut counts: Vec<(&T, usize)> = vec![];
+    for answer in answers {
+        match counts.iter_mut().find(|(counted, _)| *counted == answer) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((answer, 1)),
         }
     }
+    let max_count = counts.iter().map(|(_, count)| *count).max()?;
+    let mut most_given = counts.iter().filter(|(_, count)| *count == max_count);
+    let (answer, count) = most_given.next()?;
+    if most_given.next().is_some() || *count < quorum {
+        return None;
+    }
+    Some((*answer).clone())
 }
 
-pub async fn prompt_xai(req: Prompt) -> Result<PromptResponse, Error> {
-    let api_key = get_api_key().unwrap();
-    let response: PromptResponse = http_call(HttpMethod::POST, api_key, XAI_URL.to_string(), req)
-        .await
-        .expect("failed to prompt")?;
-    Ok(response)
+/// A parsed answer of the model.
+#[derive(Debug, Clone, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct ModelAnswer {
+    pub decision: Decision,
+    /// Between 0 and 1, only given by JSON answers.
+    pub confidence: Option<f64>,
+    pub rationale: Option<String>,
 }
 
-pub async fn prompt_deepseek(req: Prompt) -> Result<PromptResponse, Error> {
-    let api_key = get_api_key().unwrap();
-    let response: PromptResponse =
-        http_call(HttpMethod::POST, api_key, DEEPSEEK_URL.to_string(), req)
-            .await
-            .expect("failed to prompt")?;
-    Ok(response)
+/// The answer Alice asks for in `build_user_prompt`.
+#[derive(Deserialize)]
+struct JsonAnswer {
+    action: String,
+    token: Option<String>,
+    confidence: Option<f64>,
+    rationale: Option<String>,
+}
+
+/// Parses the JSON answer of the model. Models without a JSON mode may wrap
+/// the object in text or answer in plain text, in which case the decision is
+/// looked up in the words of the answer.
+pub fn parse_answer(input: &str) -> Result<ModelAnswer, String> {
+    let json = match (input.find('{'), input.rfind('}')) {
+        (Some(start), Some(end)) if start < end => {
+            serde_json::from_str::<JsonAnswer>(&input[start..=end]).ok()
+        }
+        _ => None,
+    };
+    match json {
+        Some(answer) => {
+            if let Some(confidence) = answer.confidence {
+                if !(0.0..=1.0).contains(&confidence) {
+                    return Err(format!("confidence {confidence} is not between 0 and 1"));
+                }
+            }
+            let words = format!("{} {}", answer.action, answer.token.unwrap_or_default());
+            Ok(ModelAnswer {
+                decision: parse_decision(&words)?,
+                confidence: answer.confidence,
+                rationale: answer.rationale.filter(|rationale| !rationale.is_empty()),
+            })
+        }
+        None => Ok(ModelAnswer {
+            decision: parse_decision(input)?,
+            confidence: None,
+            rationale: Some(input.trim().to_string()),
+        }),
+    }
+}
+
+/// Takes the first action word of the input and, to buy or sell, the first
+/// token named after it. Case and punctuation are ignored.
+pub fn parse_decision(input: &str) -> Result<Decision, String> {
+    let words: Vec<String> = input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    let (position, action) = words
+        .iter()
+        .enumerate()
+        .find(|(_, word)| matches!(word.as_str(), "buy" | "sell" | "hodl" | "hold"))
+        .ok_or("No action found in the answer".to_string())?;
+    if action == "hodl" || action == "hold" {
+        return Ok(Decision::Hodl);
+    }
+
+    let token = words[position + 1..]
+        .iter()
+        .find_map(|word| match word.as_str() {
+            "icp" => Some(Token::Icp),
+            "alice" => Some(Token::Alice),
+            "bob" => Some(Token::Bob),
+            _ => None,
+        })
+        .ok_or("Unknown token".to_string())?;
+
+    if token == Token::Icp {
+        return Err(format!("Cannot buy nor sell ICP",));
+    }
+
+    match action.as_str() {
+        "buy" => Ok(Decision::Buy(token)),
+        _ => Ok(Decision::Sell(token)),
+    }
+}
+
+/// Asks every provider `samples_per_provider` times with different seeds and
+/// only trades when `quorum` answers agree.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct ConsensusConfig {
+    pub samples_per_provider: u64,
+    pub quorum: u64,
+}
+
+impl ConsensusConfig {
+    /// The quorum must be reachable with the votes of the `provider_count`
+    /// configured providers.
+    pub fn validate(&self, provider_count: u64) -> Result<(), String> {
+        if !(1..=MAX_SAMPLES_PER_PROVIDER).contains(&self.samples_per_provider) {
+            return Err(format!(
+                "samples per provider must be between 1 and {MAX_SAMPLES_PER_PROVIDER}"
+            ));
+        }
+        if self.quorum == 0 {
+            return Err("quorum must be at least 1".to_string());
+        }
+        let max_votes = provider_count.saturating_mul(self.samples_per_provider);
+        if self.quorum > max_votes {
+            return Err(format!(
+                "quorum cannot exceed the {max_votes} votes of {provider_count} providers"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    /// Answers played back in order, to stand in for a model.
+    struct ScriptedProvider {
+        name: String,
+        answers: RefCell<VecDeque<Result<String, LlmError>>>,
+    }
+
+    impl ScriptedProvider {
+        fn new(name: &str, answers: Vec<Result<String, LlmError>>) -> Self {
+            Self {
+                name: name.to_string(),
+                answers: RefCell::new(answers.into()),
+            }
+        }
+    }
+
+    impl LlmProvider for ScriptedProvider {
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn model(&self) -> String {
+            "scripted".to_string()
+        }
+
+        async fn complete(&self, _messages: &[Message], _seed: i32) -> Result<String, LlmError> {
+            self.answers
+                .borrow_mut()
+                .pop_front()
+                .unwrap_or(Err(LlmError::EmptyChoices))
+        }
+    }
+
+    fn unavailable() -> LlmError {
+        LlmError::HttpStatus {
+            code: 503,
+            body_excerpt: "Service Unavailable".to_string(),
+        }
+    }
+
+    fn parse_number(content: &str) -> Result<u64, String> {
+        content
+            .trim()
+            .parse()
+            .map_err(|_| "not a number".to_string())
+    }
+
+    #[test]
+    fn should_fail_over_to_the_next_provider() {
+        let providers = vec![
+            ScriptedProvider::new("down", vec![Err(unavailable())]),
+            ScriptedProvider::new("chatty", vec![Ok("forty two".to_string())]),
+            ScriptedProvider::new("good", vec![Ok("42".to_string())]),
+            ScriptedProvider::new("unused", vec![Ok("7".to_string())]),
+        ];
+
+        let samples = block_on(prompt_with_failover(&providers, &[], 0, parse_number));
+
+        assert_eq!(
+            samples
+                .iter()
+                .map(|sample| (sample.provider.as_str(), sample.answer.is_ok()))
+                .collect::<Vec<_>>(),
+            vec![("down", false), ("chatty", false), ("good", true)]
+        );
+        assert_eq!(samples[1].raw_response, Some("forty two".to_string()));
+        assert_eq!(samples[2].answer, Ok(42));
+        assert_eq!(providers[3].answers.borrow().len(), 1);
+    }
+
+    #[test]
+    fn should_report_every_failure_when_no_provider_answers() {
+        let providers = vec![
+            ScriptedProvider::new("down", vec![Err(unavailable())]),
+            ScriptedProvider::new("empty", vec![]),
+        ];
+
+        let samples = block_on(prompt_with_failover(&providers, &[], 0, parse_number));
+
+        assert_eq!(samples.len(), 2);
+        assert!(samples.iter().all(|sample| sample.answer.is_err()));
+    }
+
+    #[test]
+    fn should_collect_one_vote_per_provider_and_seed() {
+        let providers = vec![
+            ScriptedProvider::new("a", vec![Ok("1".to_string()), Ok("2".to_string())]),
+            ScriptedProvider::new("b", vec![Err(unavailable()), Ok("2".to_string())]),
+        ];
+
+        let samples = block_on(prompt_all(&providers, &[], &[7, 8], parse_number));
+
+        assert_eq!(
+            samples
+                .into_iter()
+                .map(|sample| (sample.provider, sample.seed, sample.answer))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a".to_string(), 7, Ok(1)),
+                ("a".to_string(), 8, Ok(2)),
+                ("b".to_string(), 7, Err(unavailable())),
+                ("b".to_string(), 8, Ok(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_redact_api_keys() {
+        assert_eq!(redact_key(""), None);
+        assert_eq!(redact_key("Bearer abc"), Some("****".to_string()));
+        assert_eq!(
+            redact_key("Bearer xai-0123456789abcdef"),
+            Some("****cdef".to_string())
+        );
+    }
+
+    #[test]
+    fn should_only_agree_on_a_quorum() {
+        assert_eq!(quorum_answer(&[1, 2, 2], 2), Some(2));
+        assert_eq!(quorum_answer(&[1, 2, 2], 3), None);
+        // a tie is no consensus
+        assert_eq!(quorum_answer(&[1, 1, 2, 2], 2), None);
+        assert_eq!(quorum_answer::<u64>(&[], 1), None);
+        assert_eq!(quorum_answer(&[3], 1), Some(3));
+    }
+
+    #[test]
+    fn should_reject_unreachable_quorums() {
+        let consensus = ConsensusConfig {
+            samples_per_provider: 2,
+            quorum: 4,
+        };
+        assert!(consensus.validate(2).is_ok());
+        assert!(consensus.validate(1).is_err());
+        assert!(ConsensusConfig {
+            quorum: 5,
+            ..consensus
+        }
+        .validate(2)
+        .is_err());
+        assert!(ConsensusConfig {
+            quorum: 0,
+            ..consensus
+        }
+        .validate(2)
+        .is_err());
+    }
+
+    #[test]
+    fn should_parse_json_answers() {
+        let answer = parse_answer(
+            r#"{"action": "BUY", "token": "BOB", "confidence": 0.7, "rationale": "BOB is cheap."}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            answer,
+            ModelAnswer {
+                decision: Decision::Buy(Token::Bob),
+                confidence: Some(0.7),
+                rationale: Some("BOB is cheap.".to_string()),
+            }
+        );
+
+        let answer = parse_answer(
+            "```json\n{\"action\": \"hodl\", \"token\": null, \"rationale\": \"\"}\n```",
+        )
+        .unwrap();
+        assert_eq!(answer.decision, Decision::Hodl);
+        assert_eq!(answer.rationale, None);
+
+        assert!(parse_answer(r#"{"action": "SELL", "token": "ICP"}"#).is_err());
+        assert!(parse_answer(r#"{"action": "BUY", "token": "BOB", "confidence": 2}"#).is_err());
+    }
+
+    #[test]
+    fn should_fall_back_to_plain_text_answers() {
+        assert_eq!(
+            parse_answer("BUY BOB.").unwrap().decision,
+            Decision::Buy(Token::Bob)
+        );
+        assert_eq!(
+            parse_answer("**Sell alice**, the price dropped twice in a row.")
+                .unwrap()
+                .decision,
+            Decision::Sell(Token::Alice)
+        );
+        assert_eq!(parse_answer("HODL!").unwrap().decision, Decision::Hodl);
+        assert!(parse_answer("I cannot decide.").is_err());
+        assert!(parse_answer("BUY").is_err());
+    }
 }
@@ -1,6 +1,13 @@
+use alice::execution::TradeExecution;
+use alice::guard::ensure_role;
+use alice::llm::{configured_providers, rotate_api_key, LlmProviderConfig, RedactedProvider};
+use alice::paper::{PaperPortfolio, PaperPortfolioReport};
 use alice::state::{read_state, replace_state, State};
 use alice::tasks::{schedule_after, schedule_now, TaskType};
-use alice::{Asset, Token, TradeAction, TAKE_DECISION_DELAY};
+use alice::{
+    Asset, Config, ConsensusConfig, DeadLetter, DecisionRecord, LlmSettings, QueuedAction, Role,
+    SystemPrompt, SystemPromptInfo, Token, TradeAction, TAKE_DECISION_DELAY,
+};
 use candid::Principal;
 use ic_canisters_http_types::{HttpRequest, HttpResponse, HttpResponseBuilder};
 use ic_cdk::api::management_canister::http_request::{
@@ -10,6 +17,8 @@ use ic_cdk::{init, post_upgrade, query, update};
 use std::collections::BTreeMap;
 use strum::IntoEnumIterator;
 
+const MAX_DECISIONS_PER_PAGE: u64 = 100;
+
 fn main() {}
 
 #[init]
@@ -30,6 +39,7 @@ fn setup_timer() {
     schedule_now(TaskType::RefreshContext);
     schedule_now(TaskType::FetchQuotes);
     schedule_now(TaskType::RefreshMinerBurnRate);
+    schedule_now(TaskType::Reconcile);
 }
 
 #[export_name = "canister_global_timer"]
@@ -49,10 +59,20 @@ fn get_all_prices() -> String {
 
 #[query]
 fn last_trade_action() -> Vec<TradeAction> {
-    const LENGTH: u64 = 0;
+    const LENGTH: u64 = 10;
     alice::memory::last_trade_action(LENGTH)
 }
 
+/// The execution of the trade at `trade_id` in the trade history. Withheld
+/// trades are only shown to viewers.
+#[query]
+fn get_trade_execution(trade_id: u64) -> Option<TradeExecution> {
+    if alice::memory::get_trade_action(trade_id).is_none() && ensure_role(Role::Viewer).is_err() {
+        return None;
+    }
+    alice::memory::get_trade_execution(trade_id)
+}
+
 #[query]
 fn get_real_time_context() -> String {
     alice::build_user_prompt()
@@ -74,12 +94,44 @@ fn get_miner() -> Option<Principal> {
 }
 
 #[query]
-fn get_queue_len() -> u64 {
-    assert_eq!(
-        ic_cdk::caller(),
-        Principal::from_text("dmhsm-cyaaa-aaaal-qjrdq-cai").unwrap()
-    );
-    alice::memory::get_queue_len()
+fn get_queue_len() -> Result<u64, String> {
+    ensure_role(Role::Viewer)?;
+    Ok(alice::memory::get_queue_len())
+}
+
+/// The queued actions by id, the first one being executed next.
+#[query]
+fn get_action_queue() -> Result<Vec<(u64, QueuedAction)>, String> {
+    ensure_role(Role::Viewer)?;
+    Ok(alice::memory::get_actions())
+}
+
+#[query]
+fn get_dead_letters() -> Result<Vec<(u64, DeadLetter)>, String> {
+    ensure_role(Role::Viewer)?;
+    Ok(alice::memory::get_dead_letters())
+}
+
+/// Dead-letters a queued action, along with the following actions of its
+/// trade.
+#[update]
+fn skip_action(id: u64) -> Result<(), String> {
+    ensure_role(Role::Operator)?;
+    alice::dead_letter_action(id, format!("skipped by {}", ic_cdk::caller()))
+}
+
+#[update]
+fn requeue_action(dead_letter_id: u64) -> Result<(), String> {
+    ensure_role(Role::Operator)?;
+    alice::requeue_dead_letter(dead_letter_id)
+}
+
+/// Withdraws the balances left in the pools once no action uses them.
+#[update]
+fn reconcile() -> Result<(), String> {
+    ensure_role(Role::Operator)?;
+    schedule_now(TaskType::Reconcile);
+    Ok(())
 }
 
 #[query]
@@ -105,17 +157,165 @@ fn get_alice_portfolio() -> Vec<Asset> {
     })
 }
 
+/// Sets or rotates the API key of a provider.
 #[update(hidden = true)]
-fn set_api_key(key: String) {
-    assert_eq!(
+fn set_api_key(provider: String, key: String) -> Result<(), String> {
+    ensure_role(Role::Admin)?;
+    rotate_api_key(&provider, key)
+}
+
+/// Adds a new version of the system prompt and starts using it.
+#[update]
+fn set_system_prompt(content: String) -> Result<u64, String> {
+    ensure_role(Role::Admin)?;
+    if content.trim().is_empty() {
+        return Err("the system prompt cannot be empty".to_string());
+    }
+    Ok(alice::memory::push_system_prompt(
+        ic_cdk::api::time(),
         ic_cdk::caller(),
-        Principal::from_text("dmhsm-cyaaa-aaaal-qjrdq-cai").unwrap()
-    );
-    alice::memory::set_api_key(key);
+        content,
+    ))
+}
+
+/// Goes back to a previous version of the system prompt.
+#[update]
+fn rollback_system_prompt(version: u64) -> Result<(), String> {
+    ensure_role(Role::Admin)?;
+    if alice::memory::get_system_prompt(version).is_none() {
+        return Err(format!("unknown system prompt version {version}"));
+    }
+    alice::memory::set_active_system_prompt(version);
+    Ok(())
+}
+
+#[query]
+fn get_system_prompt(version: u64) -> Result<SystemPrompt, String> {
+    ensure_role(Role::Viewer)?;
+    alice::memory::get_system_prompt(version)
+        .ok_or_else(|| format!("unknown system prompt version {version}"))
+}
+
+/// The configured providers and system prompt versions, without the API keys
+/// and the prompts.
+#[query]
+fn get_llm_settings() -> LlmSettings {
+    LlmSettings {
+        providers: configured_providers()
+            .iter()
+            .map(RedactedProvider::from)
+            .collect(),
+        active_prompt_version: alice::memory::get_active_system_prompt(),
+        prompt_versions: alice::memory::get_system_prompts()
+            .iter()
+            .map(SystemPromptInfo::from)
+            .collect(),
+    }
+}
+
+/// Replaces the LLM providers, tried in the given order when taking a
+/// decision.
+#[update(hidden = true)]
+fn set_llm_providers(providers: Vec<LlmProviderConfig>) -> Result<(), String> {
+    ensure_role(Role::Admin)?;
+    for provider in &providers {
+        provider.validate()?;
+    }
+    if let Some(consensus) = alice::memory::get_consensus_config() {
+        // The default provider is used while none are configured.
+        consensus.validate(providers.len().max(1) as u64)?;
+    }
+    alice::memory::set_llm_providers(providers);
+    Ok(())
+}
+
+/// Grants a role to a principal, or revokes its role when passing `None`.
+#[update]
+fn set_role(principal: Principal, role: Option<Role>) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("only controllers can manage the roles".to_string());
+    }
+    alice::memory::set_role(principal, role);
+    Ok(())
+}
+
+#[query]
+fn get_roles() -> Result<BTreeMap<Principal, Role>, String> {
+    ensure_role(Role::Viewer)?;
+    Ok(alice::memory::get_roles())
+}
+
+/// Passing `None` takes decisions from the first provider giving a valid
+/// answer.
+#[update]
+fn set_consensus_config(config: Option<ConsensusConfig>) -> Result<(), String> {
+    ensure_role(Role::Operator)?;
+    if let Some(config) = &config {
+        config.validate(configured_providers().len() as u64)?;
+    }
+    alice::memory::set_consensus_config(config);
+    Ok(())
+}
+
+#[query]
+fn get_consensus_config() -> Option<ConsensusConfig> {
+    alice::memory::get_consensus_config()
+}
+
+/// Returns the decisions newest first, along with every vote.
+#[query]
+fn get_decisions(offset: u64, length: u64) -> Vec<DecisionRecord> {
+    alice::memory::get_decision_records(offset, length.min(MAX_DECISIONS_PER_PAGE))
+}
+
+#[query]
+fn get_config() -> Config {
+    alice::memory::get_config()
+}
+
+/// Turning paper trading on starts a paper portfolio from the balances of
+/// Alice, unless one is already running.
+#[update]
+fn update_config(config: Config) -> Result<(), String> {
+    ensure_role(Role::Admin)?;
+    if config.max_action_attempts == 0 {
+        return Err("max_action_attempts must be at least 1".to_string());
+    }
+    if config.paper_trading && alice::memory::get_paper_portfolio().is_none() {
+        start_paper_portfolio();
+    }
+    alice::memory::set_config(config);
+    Ok(())
+}
+
+/// Starts the paper portfolio over from the balances of Alice.
+#[update]
+fn reset_paper_portfolio() -> Result<(), String> {
+    ensure_role(Role::Admin)?;
+    start_paper_portfolio();
+    Ok(())
+}
+
+fn start_paper_portfolio() {
+    let balances = read_state(|s| s.balances.clone());
+    alice::memory::set_paper_portfolio(Some(PaperPortfolio::new(ic_cdk::api::time(), balances)));
+}
+
+/// The paper portfolio valued at the latest quotes.
+#[query]
+fn get_paper_portfolio() -> Result<Option<PaperPortfolioReport>, String> {
+    ensure_role(Role::Viewer)?;
+    let quotes: BTreeMap<Token, u64> = read_state(|s| {
+        Token::iter()
+            .filter_map(|token| s.maybe_get_last_quote(token).map(|q| (token, q.value)))
+            .collect()
+    });
+    Ok(alice::memory::get_paper_portfolio().map(|portfolio| portfolio.report(&quotes)))
 }
 
 #[update]
 async fn spawn_miner() -> Result<Principal, String> {
+    ensure_role(Role::Admin)?;
     if let Some(bob_miner) = alice::memory::get_bob_miner() {
         return Err(format!("bob miner already spawned: {bob_miner}"));
     }
@@ -129,10 +329,13 @@ async fn spawn_miner() -> Result<Principal, String> {
 fn cleanup_response(mut args: TransformArgs) -> HttpResponseCleanUp {
     args.response.headers.clear();
     if args.response.status == 200u64 {
-        let response: alice::llm::PromptResponse =
-            serde_json::from_slice(&args.response.body).unwrap();
-
-        args.response.body = serde_json::to_string(&response).unwrap().into_bytes();
+        // Drop the fields that differ between replicas. A body that is not a
+        // chat completion is emptied so that replicas agree on the failure.
+        args.response.body =
+            serde_json::from_slice::<alice::llm::PromptResponse>(&args.response.body)
+                .ok()
+                .and_then(|response| serde_json::to_vec(&response).ok())
+                .unwrap_or_default();
     }
     args.response
 }
@@ -142,6 +345,23 @@ fn http_request(req: HttpRequest) -> HttpResponse {
     use alice::logs::{Log, Priority, Sort};
     use std::str::FromStr;
 
+    if req.path() == "/decisions" {
+        let offset = req
+            .raw_query_param("offset")
+            .and_then(|arg| u64::from_str(arg).ok())
+            .unwrap_or(0);
+        let length = req
+            .raw_query_param("length")
+            .and_then(|arg| u64::from_str(arg).ok())
+            .unwrap_or(MAX_DECISIONS_PER_PAGE);
+        let decisions =
+            alice::memory::get_decision_records(offset, length.min(MAX_DECISIONS_PER_PAGE));
+        return HttpResponseBuilder::ok()
+            .header("Content-Type", "application/json; charset=utf-8")
+            .with_body_and_content_length(serde_json::to_string(&decisions).unwrap_or_default())
+            .build();
+    }
+
     let max_skip_timestamp = match req.raw_query_param("time") {
         Some(arg) => match u64::from_str(arg) {
             Ok(value) => value,
@@ -1,4 +1,10 @@
-use crate::{Action, TradeAction};
+use crate::execution::{StepOutcome, TradeExecution};
+use crate::llm::LlmProviderConfig;
+use crate::paper::PaperPortfolio;
+use crate::{
+    Action, Config, ConsensusConfig, DeadLetter, DecisionRecord, QueuedAction, Role, SystemPrompt,
+    TradeAction,
+};
 use candid::Principal;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager as MM, VirtualMemory};
 use ic_stable_structures::storable::Bound;
@@ -8,6 +14,7 @@ use ic_stable_structures::{
 };
 use std::borrow::Cow;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 
 /// A helper type implementing Storable for all
 /// serde-serializable types using the CBOR encoding.
@@ -41,6 +48,18 @@ const TRADE_HISTORY_INDX_MEM_ID: MemoryId = MemoryId::new(2);
 const TRADE_HISTORY_DATA_MEM_ID: MemoryId = MemoryId::new(3);
 const API_KEY_ID: MemoryId = MemoryId::new(4);
 const CONTEXT_ID: MemoryId = MemoryId::new(5);
+const LLM_PROVIDERS_ID: MemoryId = MemoryId::new(6);
+const CONSENSUS_ID: MemoryId = MemoryId::new(7);
+const DECISION_HISTORY_INDX_MEM_ID: MemoryId = MemoryId::new(8);
+const DECISION_HISTORY_DATA_MEM_ID: MemoryId = MemoryId::new(9);
+const CONFIG_ID: MemoryId = MemoryId::new(10);
+const SYSTEM_PROMPT_INDX_MEM_ID: MemoryId = MemoryId::new(11);
+const SYSTEM_PROMPT_DATA_MEM_ID: MemoryId = MemoryId::new(12);
+const ACTIVE_SYSTEM_PROMPT_ID: MemoryId = MemoryId::new(13);
+const ROLES_ID: MemoryId = MemoryId::new(14);
+const PAPER_PORTFOLIO_ID: MemoryId = MemoryId::new(15);
+const TRADE_EXECUTIONS_ID: MemoryId = MemoryId::new(16);
+const DEAD_LETTERS_ID: MemoryId = MemoryId::new(17);
 
 type VM = VirtualMemory<DefMem>;
 
@@ -57,8 +76,8 @@ thread_local! {
         RefCell::new(StableCell::init(mm.borrow().get(API_KEY_ID), None).unwrap())
     });
 
-    static ACTION_QUEUE: RefCell<StableBTreeMap<u64, Cbor<Action>, VM>> = MEMORY_MANAGER.with(|mm| {
-        RefCell::new(StableBTreeMap::new(mm.borrow().get(ACTION_QUEUE_ID)))
+    static ACTION_QUEUE: RefCell<StableBTreeMap<u64, Cbor<QueuedAction>, VM>> = MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableBTreeMap::init(mm.borrow().get(ACTION_QUEUE_ID)))
     });
 
     static TRADE_HISTORY: RefCell<StableLog<Cbor<TradeAction>, VM, VM>> =
@@ -72,28 +91,122 @@ thread_local! {
     static CONTEXT: RefCell<StableCell<Option<String>, VM>> = MEMORY_MANAGER.with(|mm| {
         RefCell::new(StableCell::init(mm.borrow().get(CONTEXT_ID), None).unwrap())
     });
+
+    static LLM_PROVIDERS: RefCell<StableCell<Cbor<Vec<LlmProviderConfig>>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableCell::init(mm.borrow().get(LLM_PROVIDERS_ID), Cbor(vec![])).unwrap())
+    });
+
+    static CONSENSUS: RefCell<StableCell<Cbor<Option<ConsensusConfig>>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableCell::init(mm.borrow().get(CONSENSUS_ID), Cbor(None)).unwrap())
+    });
+
+    static DECISION_HISTORY: RefCell<StableLog<Cbor<DecisionRecord>, VM, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableLog::init(
+                mm.borrow().get(DECISION_HISTORY_INDX_MEM_ID),
+                mm.borrow().get(DECISION_HISTORY_DATA_MEM_ID),
+            ).expect("failed to initialize the log"))
+    });
+
+    static CONFIG: RefCell<StableCell<Cbor<Config>, VM>> = MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableCell::init(mm.borrow().get(CONFIG_ID), Cbor(Config::default())).unwrap())
+    });
+
+    static SYSTEM_PROMPTS: RefCell<StableLog<Cbor<SystemPrompt>, VM, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableLog::init(
+                mm.borrow().get(SYSTEM_PROMPT_INDX_MEM_ID),
+                mm.borrow().get(SYSTEM_PROMPT_DATA_MEM_ID),
+            ).expect("failed to initialize the log"))
+    });
+
+    static ACTIVE_SYSTEM_PROMPT: RefCell<StableCell<Option<u64>, VM>> = MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableCell::init(mm.borrow().get(ACTIVE_SYSTEM_PROMPT_ID), None).unwrap())
+    });
+
+    static ROLES: RefCell<StableCell<Cbor<BTreeMap<Principal, Role>>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableCell::init(mm.borrow().get(ROLES_ID), Cbor(BTreeMap::new())).unwrap())
+    });
+
+    static PAPER_PORTFOLIO: RefCell<StableCell<Cbor<Option<PaperPortfolio>>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableCell::init(mm.borrow().get(PAPER_PORTFOLIO_ID), Cbor(None)).unwrap())
+    });
+
+    static TRADE_EXECUTIONS: RefCell<StableBTreeMap<u64, Cbor<TradeExecution>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(mm.borrow().get(TRADE_EXECUTIONS_ID)))
+    });
+
+    static DEAD_LETTERS: RefCell<StableBTreeMap<u64, Cbor<DeadLetter>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+            RefCell::new(StableBTreeMap::init(mm.borrow().get(DEAD_LETTERS_ID)))
+    });
 }
 
-pub fn push_trade_action(trade_action: TradeAction) {
+/// Returns the index of the trade, which identifies it.
+pub fn push_trade_action(trade_action: TradeAction) -> u64 {
     TRADE_HISTORY
         .with(|s| s.borrow().append(&Cbor(trade_action)))
-        .expect("failed to push trade_action");
+        .expect("failed to push trade_action")
+}
+
+pub fn insert_trade_execution(execution: TradeExecution) {
+    TRADE_EXECUTIONS.with(|b| b.borrow_mut().insert(execution.trade_id, Cbor(execution)));
+}
+
+pub fn get_trade_execution(trade_id: u64) -> Option<TradeExecution> {
+    TRADE_EXECUTIONS.with(|b| b.borrow().get(&trade_id).map(|execution| execution.0))
+}
+
+pub fn record_execution_step(
+    trade_id: u64,
+    ts: u64,
+    action: Action,
+    result: Result<StepOutcome, String>,
+) {
+    if let Some(mut execution) = get_trade_execution(trade_id) {
+        execution.record(ts, action, result);
+        insert_trade_execution(execution);
+    }
+}
+
+pub fn set_config(config: Config) {
+    CONFIG.with(|b| b.borrow_mut().set(Cbor(config)).unwrap());
+}
+
+pub fn get_config() -> Config {
+    CONFIG.with(|b| b.borrow().get().0.clone())
+}
+
+/// Number of trades in the public history, the latest ones are withheld.
+fn disclosed_trade_count() -> u64 {
+    let withheld_trades = get_config().withheld_trades;
+    TRADE_HISTORY.with(|s| s.borrow().len().saturating_sub(withheld_trades))
+}
+
+pub fn set_paper_portfolio(portfolio: Option<PaperPortfolio>) {
+    PAPER_PORTFOLIO.with(|b| b.borrow_mut().set(Cbor(portfolio)).unwrap());
+}
+
+pub fn get_paper_portfolio() -> Option<PaperPortfolio> {
+    PAPER_PORTFOLIO.with(|b| b.borrow().get().0.clone())
 }
 
 pub fn get_trade_action(index: u64) -> Option<TradeAction> {
-    // never return the latest trade
-    TRADE_HISTORY.with(|s| {
-        if index < s.borrow().len().saturating_sub(1) {
-            return s.borrow().get(index).map(|b| b.0);
-        }
-        None
-    })
+    if index < disclosed_trade_count() {
+        return TRADE_HISTORY.with(|s| s.borrow().get(index).map(|b| b.0));
+    }
+    None
 }
 
 pub fn last_trade_action(length: u64) -> Vec<TradeAction> {
+    let end = disclosed_trade_count();
     TRADE_HISTORY.with(|s| {
-        let start = s.borrow().len().saturating_sub(length);
-        let end = s.borrow().len();
+        let start = end.saturating_sub(length);
         let mut result: Vec<TradeAction> = vec![];
         for index in start..end {
             result.push(s.borrow().get(index).map(|b| b.0).unwrap().clone());
@@ -114,35 +227,125 @@ pub fn get_bob_miner() -> Option<Principal> {
 }
 
 pub fn set_api_key(key: String) {
-    API_KEY.with(|b| {
-        assert!(b.borrow().get().is_none());
-        b.borrow_mut().set(Some(key)).unwrap();
-    });
+    API_KEY.with(|b| b.borrow_mut().set(Some(key)).unwrap());
 }
 
 pub fn get_api_key() -> Option<String> {
     API_KEY.with(|b| b.borrow().get().clone())
 }
 
-pub fn set_context(context: String) {
-    CONTEXT.with(|b| {
-        assert!(b.borrow().get().is_none());
-        b.borrow_mut().set(Some(context)).unwrap();
+/// Passing `None` revokes the role of the principal.
+pub fn set_role(principal: Principal, role: Option<Role>) {
+    ROLES.with(|b| {
+        let mut roles = b.borrow().get().0.clone();
+        match role {
+            Some(role) => roles.insert(principal, role),
+            None => roles.remove(&principal),
+        };
+        b.borrow_mut().set(Cbor(roles)).unwrap();
+    });
+}
+
+pub fn get_role(principal: &Principal) -> Option<Role> {
+    ROLES.with(|b| b.borrow().get().0.get(principal).copied())
+}
+
+pub fn get_roles() -> BTreeMap<Principal, Role> {
+    ROLES.with(|b| b.borrow().get().0.clone())
+}
+
+pub fn set_llm_providers(providers: Vec<LlmProviderConfig>) {
+    LLM_PROVIDERS.with(|b| b.borrow_mut().set(Cbor(providers)).unwrap());
+}
+
+pub fn get_llm_providers() -> Vec<LlmProviderConfig> {
+    LLM_PROVIDERS.with(|b| b.borrow().get().0.clone())
+}
+
+pub fn set_consensus_config(config: Option<ConsensusConfig>) {
+    CONSENSUS.with(|b| b.borrow_mut().set(Cbor(config)).unwrap());
+}
+
+pub fn get_consensus_config() -> Option<ConsensusConfig> {
+    CONSENSUS.with(|b| b.borrow().get().0.clone())
+}
+
+pub fn push_decision_record(record: DecisionRecord) {
+    DECISION_HISTORY
+        .with(|s| s.borrow().append(&Cbor(record)))
+        .expect("failed to push decision record");
+}
+
+/// Returns the decisions, newest first. The decisions taken since the oldest
+/// withheld trade are withheld too.
+pub fn get_decision_records(offset: u64, length: u64) -> Vec<DecisionRecord> {
+    let withheld_since = TRADE_HISTORY.with(|s| {
+        s.borrow()
+            .get(disclosed_trade_count())
+            .map(|trade_action| trade_action.0.ts())
     });
+    DECISION_HISTORY.with(|s| {
+        let log = s.borrow();
+        (0..log.len())
+            .rev()
+            .filter_map(|index| log.get(index).map(|record| record.0))
+            .filter(|record| !matches!(withheld_since, Some(ts) if record.ts >= ts))
+            .skip(offset as usize)
+            .take(length as usize)
+            .collect()
+    })
+}
+
+/// Appends a new version of the system prompt and makes it the active one.
+pub fn push_system_prompt(ts: u64, author: Principal, content: String) -> u64 {
+    let version = SYSTEM_PROMPTS.with(|s| s.borrow().len());
+    SYSTEM_PROMPTS
+        .with(|s| {
+            s.borrow().append(&Cbor(SystemPrompt {
+                version,
+                ts,
+                author,
+                content,
+            }))
+        })
+        .expect("failed to push system prompt");
+    set_active_system_prompt(version);
+    version
+}
+
+pub fn set_active_system_prompt(version: u64) {
+    ACTIVE_SYSTEM_PROMPT.with(|b| b.borrow_mut().set(Some(version)).unwrap());
+}
+
+pub fn get_active_system_prompt() -> Option<u64> {
+    ACTIVE_SYSTEM_PROMPT.with(|b| *b.borrow().get())
 }
 
+pub fn get_system_prompt(version: u64) -> Option<SystemPrompt> {
+    SYSTEM_PROMPTS.with(|s| s.borrow().get(version).map(|prompt| prompt.0))
+}
+
+pub fn get_system_prompts() -> Vec<SystemPrompt> {
+    SYSTEM_PROMPTS.with(|s| s.borrow().iter().map(|prompt| prompt.0).collect())
+}
+
+/// The active system prompt, falling back to the context set before the
+/// system prompt was versioned.
 pub fn get_context() -> Option<String> {
-    CONTEXT.with(|b| b.borrow().get().clone())
+    match get_active_system_prompt().and_then(get_system_prompt) {
+        Some(prompt) => Some(prompt.content),
+        None => CONTEXT.with(|b| b.borrow().get().clone()),
+    }
 }
 
-pub fn push_action(action: Action) {
+pub fn push_action(action: QueuedAction) {
     ACTION_QUEUE.with(|b| {
         let new_id = b.borrow().last_key_value().map(|(k, _)| k + 1).unwrap_or(0);
         b.borrow_mut().insert(new_id, Cbor(action));
     });
 }
 
-pub fn push_actions(actions: Vec<Action>) {
+pub fn push_actions(actions: Vec<QueuedAction>) {
     ACTION_QUEUE.with(|b| {
         for action in actions {
             let new_id = b.borrow().last_key_value().map(|(k, _)| k + 1).unwrap_or(0);
@@ -151,14 +354,120 @@ pub fn push_actions(actions: Vec<Action>) {
     });
 }
 
-pub fn pop_front_action() -> Option<Action> {
-    ACTION_QUEUE.with(|b| b.borrow_mut().pop_first().map(|(_, action)| action.0))
+pub fn next_action() -> Option<(u64, QueuedAction)> {
+    ACTION_QUEUE.with(|b| b.borrow().first_key_value().map(|(k, v)| (k, v.0)))
 }
 
-pub fn next_action() -> Option<Action> {
-    ACTION_QUEUE.with(|b| b.borrow().first_key_value().map(|(_, v)| v.0))
+pub fn get_actions() -> Vec<(u64, QueuedAction)> {
+    ACTION_QUEUE.with(|b| b.borrow().iter().map(|(k, v)| (k, v.0)).collect())
+}
+
+/// Only updates actions still in the queue.
+pub fn update_action(id: u64, action: QueuedAction) {
+    ACTION_QUEUE.with(|b| {
+        if b.borrow().contains_key(&id) {
+            b.borrow_mut().insert(id, Cbor(action));
+        }
+    });
+}
+
+pub fn remove_action(id: u64) -> Option<QueuedAction> {
+    ACTION_QUEUE.with(|b| b.borrow_mut().remove(&id).map(|action| action.0))
+}
+
+pub fn push_dead_letter(dead_letter: DeadLetter) {
+    DEAD_LETTERS.with(|b| {
+        let new_id = b.borrow().last_key_value().map(|(k, _)| k + 1).unwrap_or(0);
+        b.borrow_mut().insert(new_id, Cbor(dead_letter));
+    });
+}
+
+pub fn get_dead_letters() -> Vec<(u64, DeadLetter)> {
+    DEAD_LETTERS.with(|b| b.borrow().iter().map(|(k, v)| (k, v.0)).collect())
+}
+
+pub fn remove_dead_letter(id: u64) -> Option<DeadLetter> {
+    DEAD_LETTERS.with(|b| b.borrow_mut().remove(&id).map(|dead_letter| dead_letter.0))
 }
 
 pub fn get_queue_len() -> u64 {
     ACTION_QUEUE.with(|b| b.borrow_mut().len())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Token;
+
+    /// Pushes `count` trades at ts 10, 20, ... each preceded by a
+    /// decision at the same ts.
+    fn push_trades(count: u64, withheld_trades: u64) {
+        set_config(Config {
+            withheld_trades,
+            ..Default::default()
+        });
+        for i in 1..=count {
+            let trade_action = TradeAction::Buy {
+                token: Token::Bob,
+                amount: i,
+                ts: 10 * i,
+                rationale: None,
+            };
+            push_decision_record(DecisionRecord {
+                ts: 10 * i,
+                user_prompt: String::new(),
+                quorum: 1,
+                votes: vec![],
+                result: Ok(trade_action.clone()),
+                actions: vec![],
+                paper_trading: false,
+            });
+            push_trade_action(trade_action);
+        }
+    }
+
+    fn decision_ts(offset: u64, length: u64) -> Vec<u64> {
+        get_decision_records(offset, length)
+            .iter()
+            .map(|record| record.ts)
+            .collect()
+    }
+
+    #[test]
+    fn should_disclose_every_decision_without_withheld_trades() {
+        push_trades(3, 0);
+        assert_eq!(disclosed_trade_count(), 3);
+        assert_eq!(decision_ts(0, 10), vec![30, 20, 10]);
+    }
+
+    #[test]
+    fn should_withhold_the_decision_of_the_latest_trade() {
+        push_trades(3, 1);
+        assert_eq!(disclosed_trade_count(), 2);
+        assert_eq!(decision_ts(0, 10), vec![20, 10]);
+    }
+
+    #[test]
+    fn should_withhold_the_decisions_of_the_latest_trades() {
+        push_trades(5, 3);
+        assert_eq!(disclosed_trade_count(), 2);
+        assert_eq!(decision_ts(0, 10), vec![20, 10]);
+    }
+
+    #[test]
+    fn should_withhold_everything_when_every_trade_is_withheld() {
+        push_trades(2, 3);
+        assert_eq!(disclosed_trade_count(), 0);
+        assert!(decision_ts(0, 10).is_empty());
+    }
+
+    #[test]
+    fn should_page_the_disclosed_decisions() {
+        push_trades(5, 1);
+        assert_eq!(decision_ts(0, 2), vec![40, 30]);
+        assert_eq!(decision_ts(2, 2), vec![20, 10]);
+        assert_eq!(decision_ts(3, 10), vec![10]);
+        assert!(decision_ts(4, 10).is_empty());
+        assert!(decision_ts(0, 0).is_empty());
+    }
+}
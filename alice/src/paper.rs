@@ -0,0 +1,200 @@
+use crate::{Token, TradeAction};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const E8S: u128 = 100_000_000;
+
+/// A virtual portfolio trading at the latest quotes, to evaluate decisions
+/// without touching funds.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct PaperPortfolio {
+    pub started_at: u64,
+    pub starting_balances: BTreeMap<Token, u64>,
+    pub balances: BTreeMap<Token, u64>,
+    pub trades: Vec<PaperTrade>,
+}
+
+/// A simulated trade, `quote` is the ICP amount in e8s paid for 1 token.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+pub struct PaperTrade {
+    pub trade: TradeAction,
+    pub quote: u64,
+    pub sold: (Token, u64),
+    pub bought: (Token, u64),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Deserialize)]
+pub struct PaperPortfolioReport {
+    pub portfolio: PaperPortfolio,
+    /// Value of the portfolio in ICP e8s, at the latest quotes.
+    pub value_e8s: Option<u64>,
+    /// Value of the starting balances in ICP e8s, at the latest quotes.
+    pub hodl_value_e8s: Option<u64>,
+    /// Gain over holding the starting balances.
+    pub pnl_e8s: Option<i64>,
+}
+
+impl PaperPortfolio {
+    pub fn new(started_at: u64, balances: BTreeMap<Token, u64>) -> Self {
+        Self {
+            started_at,
+            starting_balances: balances.clone(),
+            balances,
+            trades: vec![],
+        }
+    }
+
+    fn balance(&self, token: Token) -> u64 {
+        self.balances.get(&token).copied().unwrap_or(0)
+    }
+
+    /// Fills the trade at `quote`, paying the ledger fees that the deposit and
+    /// the withdrawal of the real trade pay.
+    pub fn apply(&mut self, trade: &TradeAction, quote: u64) -> Result<PaperTrade, String> {
+        if quote == 0 {
+            return Err("cannot trade at a zero quote".to_string());
+        }
+        let (sold, bought, amount) = match trade {
+            TradeAction::Buy { token, amount, .. } => (Token::Icp, *token, *amount),
+            TradeAction::Sell { token, amount, .. } => (*token, Token::Icp, *amount),
+        };
+        if self.balance(sold) < amount {
+            return Err(format!(
+                "paper balance of {sold} too low to trade {amount}, got {}",
+                self.balance(sold)
+            ));
+        }
+        let amount_in = amount.saturating_sub(2 * sold.fee_e8s()) as u128;
+        let amount_out = match sold {
+            Token::Icp => amount_in * E8S / quote as u128,
+            _ => amount_in * quote as u128 / E8S,
+        };
+        let amount_out = u64::try_from(amount_out)
+            .unwrap_or(u64::MAX)
+            .saturating_sub(2 * bought.fee_e8s());
+
+        *self.balances.entry(sold).or_default() -= amount;
+        *self.balances.entry(bought).or_default() += amount_out;
+        let paper_trade = PaperTrade {
+            trade: trade.clone(),
+            quote,
+            sold: (sold, amount),
+            bought: (bought, amount_out),
+        };
+        self.trades.push(paper_trade.clone());
+        Ok(paper_trade)
+    }
+
+    pub fn report(self, quotes: &BTreeMap<Token, u64>) -> PaperPortfolioReport {
+        let value_e8s = value_in_icp(&self.balances, quotes);
+        let hodl_value_e8s = value_in_icp(&self.starting_balances, quotes);
+        let pnl_e8s = value_e8s
+            .zip(hodl_value_e8s)
+            .map(|(value, hodl_value)| value as i64 - hodl_value as i64);
+        PaperPortfolioReport {
+            portfolio: self,
+            value_e8s,
+            hodl_value_e8s,
+            pnl_e8s,
+        }
+    }
+}
+
+/// Value of the balances in ICP e8s, `None` if a token held has no quote.
+pub fn value_in_icp(balances: &BTreeMap<Token, u64>, quotes: &BTreeMap<Token, u64>) -> Option<u64> {
+    let mut value: u128 = 0;
+    for (token, balance) in balances {
+        value += match token {
+            Token::Icp => *balance as u128,
+            _ if *balance == 0 => 0,
+            _ => *balance as u128 * *quotes.get(token)? as u128 / E8S,
+        };
+    }
+    Some(value.min(u64::MAX as u128) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portfolio() -> PaperPortfolio {
+        PaperPortfolio::new(
+            0,
+            BTreeMap::from([(Token::Icp, 10 * E8S as u64), (Token::Bob, 0)]),
+        )
+    }
+
+    #[test]
+    fn should_buy_and_sell_at_the_quote_minus_fees() {
+        let mut portfolio = portfolio();
+        // 1 BOB = 0.5 ICP
+        let quote = 50_000_000;
+
+        let buy = portfolio
+            .apply(
+                &TradeAction::Buy {
+                    token: Token::Bob,
+                    amount: E8S as u64,
+                    ts: 1,
+                    rationale: None,
+                },
+                quote,
+            )
+            .unwrap();
+        assert_eq!(buy.sold, (Token::Icp, 100_000_000));
+        assert_eq!(buy.bought, (Token::Bob, 199_960_000 - 2_000_000));
+        assert_eq!(portfolio.balance(Token::Icp), 900_000_000);
+
+        let sell = portfolio
+            .apply(
+                &TradeAction::Sell {
+                    token: Token::Bob,
+                    amount: 100_000_000,
+                    ts: 2,
+                    rationale: None,
+                },
+                quote,
+            )
+            .unwrap();
+        assert_eq!(sell.bought, (Token::Icp, 49_000_000 - 20_000));
+        assert_eq!(portfolio.balance(Token::Bob), 97_960_000);
+        assert_eq!(portfolio.trades.len(), 2);
+    }
+
+    #[test]
+    fn should_not_trade_more_than_the_balance() {
+        let mut portfolio = portfolio();
+        let before = portfolio.clone();
+
+        let result = portfolio.apply(
+            &TradeAction::Sell {
+                token: Token::Bob,
+                amount: 1,
+                ts: 1,
+                rationale: None,
+            },
+            50_000_000,
+        );
+
+        assert!(result.is_err());
+        assert_eq!(portfolio, before);
+    }
+
+    #[test]
+    fn should_compare_with_holding() {
+        let mut portfolio = portfolio();
+        portfolio.balances =
+            BTreeMap::from([(Token::Icp, 5 * E8S as u64), (Token::Bob, 20 * E8S as u64)]);
+
+        let report = portfolio.report(&BTreeMap::from([(Token::Bob, 30_000_000)]));
+
+        assert_eq!(report.value_e8s, Some(11 * E8S as u64));
+        assert_eq!(report.hodl_value_e8s, Some(10 * E8S as u64));
+        assert_eq!(report.pnl_e8s, Some(E8S as i64));
+        assert_eq!(
+            value_in_icp(&BTreeMap::from([(Token::Bob, 1)]), &BTreeMap::new()),
+            None
+        );
+    }
+}
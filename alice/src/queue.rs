@@ -0,0 +1,257 @@
+use crate::logs::INFO;
+use crate::memory::{
+    get_actions, push_action, push_dead_letter, remove_action, remove_dead_letter,
+};
+use crate::tasks::{schedule_now, TaskType};
+use crate::{timestamp_nanos, Action};
+use candid::CandidType;
+use ic_canister_log::log;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Errors kept in the history of a queued action.
+const MAX_ACTION_ERRORS: usize = 10;
+
+#[derive(Debug, Clone, CandidType, Deserialize, Serialize, Eq, PartialEq)]
+pub struct ActionError {
+    pub ts: u64,
+    pub error: String,
+}
+
+/// An action waiting in the queue, along with the trade it executes.
+#[derive(Debug, Clone, CandidType, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(from = "StoredAction")]
+pub struct QueuedAction {
+    pub trade_id: Option<u64>,
+    pub action: Action,
+    /// Number of failed attempts.
+    pub attempts: u64,
+    /// The latest errors, oldest first.
+    pub errors: Vec<ActionError>,
+}
+
+impl QueuedAction {
+    pub fn new(trade_id: Option<u64>, action: Action) -> Self {
+        Self {
+            trade_id,
+            action,
+            attempts: 0,
+            errors: vec![],
+        }
+    }
+
+    pub(crate) fn record_failure(&mut self, ts: u64, error: String) {
+        self.attempts += 1;
+        self.errors.push(ActionError { ts, error });
+        if self.errors.len() > MAX_ACTION_ERRORS {
+            self.errors.remove(0);
+        }
+    }
+}
+
+/// Actions were queued on their own before being linked to their trade.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredAction {
+    Queued {
+        trade_id: Option<u64>,
+        action: Action,
+        #[serde(default)]
+        attempts: u64,
+        #[serde(default)]
+        errors: Vec<ActionError>,
+    },
+    Legacy(Action),
+}
+
+impl From<StoredAction> for QueuedAction {
+    fn from(stored: StoredAction) -> Self {
+        match stored {
+            StoredAction::Queued {
+                trade_id,
+                action,
+                attempts,
+                errors,
+            } => Self {
+                trade_id,
+                action,
+                attempts,
+                errors,
+            },
+            StoredAction::Legacy(action) => Self::new(None, action),
+        }
+    }
+}
+
+/// An action taken out of the queue after failing too often or being skipped.
+#[derive(Debug, Clone, CandidType, Deserialize, Serialize, Eq, PartialEq)]
+pub struct DeadLetter {
+    pub ts: u64,
+    pub reason: String,
+    pub queued: QueuedAction,
+}
+
+/// Waits twice as long after each failed attempt, from 5 seconds up to 30
+/// minutes.
+pub fn retry_delay(attempts: u64) -> Duration {
+    const MAX_RETRY_DELAY_SECS: u64 = 30 * 60;
+    let secs = 5_u64.saturating_mul(1 << attempts.min(20));
+    Duration::from_secs(secs.min(MAX_RETRY_DELAY_SECS))
+}
+
+/// Moves the queued action to the dead letters, along with the following
+/// actions of its trade which cannot succeed without it.
+pub fn dead_letter_action(id: u64, reason: String) -> Result<(), String> {
+    let queued = remove_action(id).ok_or(format!("no queued action {id}"))?;
+    let ts = timestamp_nanos();
+    let trade_id = queued.trade_id;
+    log!(
+        INFO,
+        "[ProcessLogic] Dead-lettered {:?}: {reason}",
+        queued.action
+    );
+    // The failed action goes first so that requeuing the dead letters in
+    // order replays the trade in order.
+    push_dead_letter(DeadLetter { ts, reason, queued });
+    if let Some(trade_id) = trade_id {
+        for (next_id, next) in get_actions() {
+            if next_id > id && next.trade_id == Some(trade_id) {
+                remove_action(next_id);
+                push_dead_letter(DeadLetter {
+                    ts,
+                    reason: format!("action {id} of trade {trade_id} was dead-lettered"),
+                    queued: next,
+                });
+            }
+        }
+    }
+    schedule_now(TaskType::Reconcile);
+    Ok(())
+}
+
+/// Puts a dead-lettered action back at the end of the queue.
+pub fn requeue_dead_letter(id: u64) -> Result<(), String> {
+    let dead_letter = remove_dead_letter(id).ok_or(format!("no dead letter {id}"))?;
+    push_action(QueuedAction {
+        attempts: 0,
+        ..dead_letter.queued
+    });
+    schedule_now(TaskType::ProcessLogic);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{get_dead_letters, push_actions};
+    use crate::Token;
+    use candid::Principal;
+
+    #[test]
+    fn should_back_off_retries() {
+        assert_eq!(retry_delay(0), Duration::from_secs(5));
+        assert_eq!(retry_delay(3), Duration::from_secs(40));
+        assert_eq!(retry_delay(9), Duration::from_secs(30 * 60));
+        assert_eq!(retry_delay(u64::MAX), Duration::from_secs(30 * 60));
+    }
+
+    #[test]
+    fn should_decode_actions_queued_before_their_trade() {
+        let action = Action::Withdraw {
+            pool_id: Principal::anonymous(),
+            token: Token::Bob,
+            amount: 42,
+        };
+        let mut legacy = vec![];
+        ciborium::ser::into_writer(&action, &mut legacy).unwrap();
+
+        let queued: QueuedAction = ciborium::de::from_reader(legacy.as_slice()).unwrap();
+        assert_eq!(queued, QueuedAction::new(None, action.clone()));
+
+        let mut failing = QueuedAction::new(Some(7), action);
+        for i in 0..=MAX_ACTION_ERRORS as u64 {
+            failing.record_failure(i, format!("error {i}"));
+        }
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&failing, &mut bytes).unwrap();
+        let decoded: QueuedAction = ciborium::de::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(decoded, failing);
+        assert_eq!(decoded.attempts, MAX_ACTION_ERRORS as u64 + 1);
+        assert_eq!(decoded.errors.len(), MAX_ACTION_ERRORS);
+        assert_eq!(decoded.errors[0].error, "error 1");
+    }
+
+    #[test]
+    fn should_dead_letter_and_requeue_a_trade_in_order() {
+        let pool_id = Principal::anonymous();
+        let steps = vec![
+            Action::Icrc2Approve {
+                pool_id,
+                amount: 10,
+                token: Token::Icp,
+            },
+            Action::DepositFrom {
+                pool_id,
+                ledger_id: Principal::management_canister(),
+                amount: 10,
+            },
+            Action::Swap {
+                pool_id,
+                from: Token::Icp,
+                to: Token::Bob,
+                amount: 10,
+                zero_for_one: true,
+            },
+            Action::Withdraw {
+                pool_id,
+                token: Token::Bob,
+                amount: 5,
+            },
+        ];
+        let other = QueuedAction::new(Some(8), steps[0].clone());
+        push_action(QueuedAction::new(Some(7), steps[0].clone()));
+        push_action(other.clone());
+        push_actions(
+            steps[1..]
+                .iter()
+                .map(|step| QueuedAction::new(Some(7), step.clone()))
+                .collect(),
+        );
+
+        // The approval succeeded, the deposit keeps failing.
+        remove_action(0);
+        dead_letter_action(2, "deposit failed".to_string()).unwrap();
+
+        assert_eq!(get_actions(), vec![(1, other.clone())]);
+        let dead_letters = get_dead_letters();
+        assert_eq!(
+            dead_letters
+                .iter()
+                .map(|(_, dead_letter)| dead_letter.queued.action.clone())
+                .collect::<Vec<_>>(),
+            steps[1..].to_vec()
+        );
+        assert_eq!(dead_letters[0].1.reason, "deposit failed");
+        assert_eq!(
+            dead_letters[1].1.reason,
+            "action 2 of trade 7 was dead-lettered"
+        );
+
+        for (id, _) in dead_letters {
+            requeue_dead_letter(id).unwrap();
+        }
+        assert!(get_dead_letters().is_empty());
+        let requeued: Vec<_> = get_actions()
+            .into_iter()
+            .skip(1)
+            .map(|(_, queued)| queued)
+            .collect();
+        assert_eq!(
+            requeued,
+            steps[1..]
+                .iter()
+                .map(|step| QueuedAction::new(Some(7), step.clone()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -116,17 +116,26 @@ impl State {
     }
 
     pub fn maybe_get_asset_value_in_portfolio(&self, token: Token) -> Option<u64> {
+        self.maybe_get_asset_value(&self.balances, token)
+    }
+
+    fn maybe_get_asset_value(&self, balances: &BTreeMap<Token, u64>, token: Token) -> Option<u64> {
+        let balance = balances.get(&token).copied().unwrap_or(0);
         if token == Token::Icp {
-            return Some(read_state(|s| s.get_balance(token)));
+            return Some(balance);
         }
         self.maybe_get_last_quote(token)
-            .map(|quote| self.get_balance(token) * quote.value / 100_000_000)
+            .map(|quote| balance * quote.value / 100_000_000)
     }
 
     pub fn maybe_portfolio_value(&self) -> Option<u64> {
+        self.maybe_portfolio_value_of(&self.balances)
+    }
+
+    fn maybe_portfolio_value_of(&self, balances: &BTreeMap<Token, u64>) -> Option<u64> {
         let mut res = 0;
         for token in Token::iter() {
-            if let Some(value) = self.maybe_get_asset_value_in_portfolio(token) {
+            if let Some(value) = self.maybe_get_asset_value(balances, token) {
                 res += value;
             } else {
                 return None;
@@ -182,7 +191,12 @@ impl State {
     }
 
     pub fn amount_to_buy(&self, token: Token) -> u64 {
-        let portfolio_value = self.maybe_portfolio_value().unwrap_or(0) as f64;
+        self.amount_to_buy_with(&self.balances, token)
+    }
+
+    /// Sizes a buy as if Alice held `balances`.
+    pub fn amount_to_buy_with(&self, balances: &BTreeMap<Token, u64>, token: Token) -> u64 {
+        let portfolio_value = self.maybe_portfolio_value_of(balances).unwrap_or(0) as f64;
         let quote = self
             .maybe_get_last_quote(token)
             .map(|q| q.value)
@@ -193,7 +207,7 @@ impl State {
         if portfolio_value + quote <= 0.0 {
             return 0;
         }
-        let max_trade = self.get_balance(token) / 10;
+        let max_trade = balances.get(&token).copied().unwrap_or(0) / 10;
         if var <= 0.0 {
             return max_trade;
         }
@@ -204,10 +218,15 @@ impl State {
     }
 
     pub fn suppress_old_quotes(&mut self) {
+        self.suppress_quotes_older_than(timestamp_nanos());
+    }
+
+    /// Only keeps the quotes of the 30 days before `now`.
+    pub fn suppress_quotes_older_than(&mut self, now: u64) {
         for token in Token::iter() {
             if let Some(quotes) = self.token_to_quotes.get_mut(&token) {
                 while let Some(quote) = quotes.front() {
-                    if timestamp_nanos() > quote.ts + 30 * 24 * ONE_HOUR_NANOS {
+                    if now > quote.ts + 30 * 24 * ONE_HOUR_NANOS {
                         quotes.pop_front();
                     } else {
                         break;
@@ -20,6 +20,7 @@ pub enum TaskType {
     TakeDecision,
     FetchQuotes,
     RefreshMinerBurnRate,
+    Reconcile,
 }
 
 #[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, CandidType)]
@@ -116,7 +117,7 @@ impl TaskQueue {
 
 /// Schedules a task for execution after the given delay.
 pub fn schedule_after(delay: Duration, work: TaskType) {
-    let now_nanos = ic_cdk::api::time();
+    let now_nanos = crate::timestamp_nanos();
     let execute_at = now_nanos.saturating_add(delay.as_secs() * SEC_NANOS);
 
     let execution_time = TASKS.with(|t| t.borrow_mut().schedule_at(execute_at, work));
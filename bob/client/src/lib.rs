@@ -0,0 +1,108 @@
+//! Typed clients to call the BoB minter and miners from other canisters.
+
+pub mod miner;
+pub mod minter;
+
+pub use ic_cdk::api::call::CallResult;
+
+/// Declares a client struct with one typed async method per canister method,
+/// along with `candid_interface` rendering the declared methods as a did
+/// service so that they can be checked against the canister interface.
+macro_rules! canister_client {
+    (
+        $(#[$meta:meta])*
+        pub struct $client:ident ($($init:ty),*) {
+            $(
+                $mode:ident fn $method:ident($($arg:ident : $arg_ty:ty),*) $(-> $ret:ty)?;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct $client {
+            pub canister_id: candid::Principal,
+        }
+
+        impl $client {
+            pub fn new(canister_id: candid::Principal) -> Self {
+                Self { canister_id }
+            }
+
+            $(
+                pub async fn $method(
+                    &self,
+                    $($arg: $arg_ty),*
+                ) -> $crate::CallResult<$crate::reply!($($ret)?)> {
+                    $crate::into_reply!(
+                        ic_cdk::api::call::call::<_, $crate::reply_tuple!($($ret)?)>(
+                            self.canister_id,
+                            stringify!($method),
+                            ($($arg,)*),
+                        )
+                        .await?
+                        $(, $ret)?
+                    )
+                }
+            )*
+
+            pub fn candid_interface() -> String {
+                use candid::types::{Function, Type, TypeInner};
+
+                let mut env = candid::types::internal::TypeContainer::new();
+                let mut service = Vec::<(String, Type)>::new();
+                $(
+                    let function = Function {
+                        modes: $crate::func_modes!($mode),
+                        args: vec![$(env.add::<$arg_ty>()),*],
+                        rets: vec![$(env.add::<$ret>())?],
+                    };
+                    service.push((stringify!($method).to_string(), TypeInner::Func(function).into()));
+                )*
+                service.sort_unstable_by_key(|(name, _)| name.clone());
+                let service: Type = TypeInner::Service(service).into();
+                let init: Vec<Type> = vec![$(env.add::<$init>()),*];
+                let actor: Type = TypeInner::Class(init, service).into();
+                candid::pretty::candid::compile(&env.env, &Some(actor))
+            }
+        }
+    };
+}
+
+macro_rules! reply {
+    () => {
+        ()
+    };
+    ($ret:ty) => {
+        $ret
+    };
+}
+
+macro_rules! reply_tuple {
+    () => {
+        ()
+    };
+    ($ret:ty) => {
+        ($ret,)
+    };
+}
+
+macro_rules! into_reply {
+    ($reply:expr) => {{
+        $reply;
+        Ok(())
+    }};
+    ($reply:expr, $ret:ty) => {
+        Ok($reply.0)
+    };
+}
+
+macro_rules! func_modes {
+    (query) => {
+        vec![candid::types::FuncMode::Query]
+    };
+    (update) => {
+        vec![]
+    };
+}
+
+pub(crate) use {canister_client, func_modes, into_reply, reply, reply_tuple};
@@ -0,0 +1,35 @@
+use crate::canister_client;
+use bob_types::miner::{MinerSettings, OperatorPermission, Round, State, StatsV2};
+use candid::Principal;
+
+canister_client! {
+    /// Client of a BoB miner, see `bob/miner-v2/miner.did`.
+    pub struct MinerClient (Principal) {
+        query fn get_operator_permissions(operator: Principal) -> Vec<OperatorPermission>;
+        query fn get_round_history(offset: u64, length: u64) -> Vec<Round>;
+        query fn get_state() -> State;
+        query fn get_statistics_v2() -> StatsV2;
+        update fn notify_block_won(rewards: u64);
+        update fn push_challenge(challenge: [u8; 32], difficulty: u64);
+        update fn set_operator(operator: Principal, permissions: Vec<OperatorPermission>);
+        update fn update_miner_settings(settings: MinerSettings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid_parser::utils::{service_equal, CandidSource};
+
+    #[test]
+    fn test_client_interface_matches_declared_interface_exactly() {
+        let declared_interface = include_str!("../../miner-v2/miner.did");
+        let declared_interface = CandidSource::Text(declared_interface);
+
+        let client_interface_str = MinerClient::candid_interface();
+        let client_interface = CandidSource::Text(&client_interface_str);
+
+        let result = service_equal(declared_interface, client_interface);
+        assert!(result.is_ok(), "{:?}\n\n", result.unwrap_err());
+    }
+}
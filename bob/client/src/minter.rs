@@ -0,0 +1,119 @@
+use crate::{canister_client, CallResult};
+use bob_types::minter::{
+    AutoTopUp, Block, Challenge, Config, CurrentBlockStatus, EmissionInfo, LeaderBoardEntry, Miner,
+    MinerRefill, MinerRollout, MinerRolloutArgs, MinerWasmVersion, Payment, PoolStats, Stats,
+    SubmissionMetrics,
+};
+use candid::Principal;
+
+/// The BoB minter on mainnet, `6lnhz-oaaaa-aaaas-aabkq-cai`.
+pub const MAINNET_MINTER_ID: Principal =
+    Principal::from_slice(&[0x00, 0x00, 0x00, 0x00, 0x02, 0x40, 0x00, 0x55, 0x01, 0x01]);
+
+canister_client! {
+    /// Client of the BoB minter, see `bob/minter-v2/bob.did`.
+    pub struct MinterClient () {
+        update fn commit_miner_wasm(version: u64, sha256: String) -> Result<(), String>;
+        query fn filter_out_known_index(indices: Vec<u64>) -> Vec<u64>;
+        query fn get_challenge() -> Option<Challenge>;
+        query fn get_config() -> Config;
+        query fn get_current_block_status() -> CurrentBlockStatus;
+        query fn get_emission_info() -> EmissionInfo;
+        query fn get_latest_blocks() -> Vec<Block>;
+        query fn get_leader_board() -> Vec<LeaderBoardEntry>;
+        query fn get_miner_auto_top_up(miner: Principal) -> Option<AutoTopUp>;
+        query fn get_miner_refill_log(miner: Principal) -> Vec<MinerRefill>;
+        query fn get_miner_rollout() -> Option<MinerRollout>;
+        query fn get_miner_wasm_versions() -> Vec<MinerWasmVersion>;
+        query fn get_miners(of: Principal) -> Vec<Miner>;
+        query fn get_pool_statistic() -> PoolStats;
+        query fn get_statistics() -> Stats;
+        query fn get_submission_metrics() -> SubmissionMetrics;
+        query fn get_wasm_len() -> u64;
+        query fn hours_left_in_pool(maybe_target: Option<Principal>) -> u64;
+        update fn join_pool(block_index: u64) -> Result<(), String>;
+        update fn join_pool_with_payment(payment: Payment) -> Result<(), String>;
+        update fn set_miner_auto_top_up(
+            miner: Principal,
+            auto_top_up: Option<AutoTopUp>
+        ) -> Result<(), String>;
+        update fn spawn_miner(block_index: u64) -> Result<Principal, String>;
+        update fn spawn_miner_with_payment(payment: Payment) -> Result<Principal, String>;
+        update fn spawn_miners(
+            block_index: u64,
+            count: u64
+        ) -> Result<Vec<Result<Principal, String>>, String>;
+        update fn start_miner_rollout(args: MinerRolloutArgs) -> Result<(), String>;
+        update fn submit_burned_cycles(cycles: u64) -> Result<(), String>;
+        update fn submit_solution(challenge: [u8; 32], nonce: u64) -> Result<(), String>;
+        update fn update_config(config: Config) -> Result<(), String>;
+        update fn upgrade_miner(miner: Principal) -> Result<(), String>;
+        update fn upload_miner_wasm_chunk(version: u64, chunk: Vec<u8>) -> Result<u64, String>;
+    }
+}
+
+impl MinterClient {
+    pub fn mainnet() -> Self {
+        Self::new(MAINNET_MINTER_ID)
+    }
+
+    /// Spawns a miner paid with the attached cycles.
+    pub async fn spawn_miner_with_cycles(
+        &self,
+        cycles: u128,
+    ) -> CallResult<Result<Principal, String>> {
+        let (result,) = ic_cdk::api::call::call_with_payment128(
+            self.canister_id,
+            "spawn_miner_with_payment",
+            (Payment::Cycles,),
+            cycles,
+        )
+        .await?;
+        Ok(result)
+    }
+
+    /// Joins the pool for as many days as the attached cycles pay for.
+    pub async fn join_pool_with_cycles(&self, cycles: u128) -> CallResult<Result<(), String>> {
+        let (result,) = ic_cdk::api::call::call_with_payment128(
+            self.canister_id,
+            "join_pool_with_payment",
+            (Payment::Cycles,),
+            cycles,
+        )
+        .await?;
+        Ok(result)
+    }
+
+    /// Submits the attached cycles, the minter burns the cycles it accepts.
+    pub async fn submit_burned_cycles_with_payment(
+        &self,
+        cycles: u128,
+    ) -> CallResult<Result<(), String>> {
+        let (result,) = ic_cdk::api::call::call_with_payment128(
+            self.canister_id,
+            "submit_burned_cycles",
+            (cycles.min(u64::MAX as u128) as u64,),
+            cycles,
+        )
+        .await?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid_parser::utils::{service_equal, CandidSource};
+
+    #[test]
+    fn test_client_interface_matches_declared_interface_exactly() {
+        let declared_interface = include_str!("../../minter-v2/bob.did");
+        let declared_interface = CandidSource::Text(declared_interface);
+
+        let client_interface_str = MinterClient::candid_interface();
+        let client_interface = CandidSource::Text(&client_interface_str);
+
+        let result = service_equal(declared_interface, client_interface);
+        assert!(result.is_ok(), "{:?}\n\n", result.unwrap_err());
+    }
+}
@@ -0,0 +1,22 @@
+//! Canister of the integration tests forwarding calls with cycles attached,
+//! since ingress messages cannot carry cycles.
+
+use candid::Principal;
+use ic_cdk::update;
+
+fn main() {}
+
+/// Calls the method with the candid encoded argument and the cycles
+/// attached. Returns the raw reply along with the cycles refunded.
+#[update]
+async fn call_with_cycles(
+    canister: Principal,
+    method: String,
+    arg: Vec<u8>,
+    cycles: u128,
+) -> Result<(Vec<u8>, u128), String> {
+    let reply = ic_cdk::api::call::call_raw128(canister, &method, arg, cycles)
+        .await
+        .map_err(|(code, msg)| format!("call failed ({}): {msg}", code as i32))?;
+    Ok((reply, ic_cdk::api::call::msg_cycles_refunded128()))
+}
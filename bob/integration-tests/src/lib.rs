@@ -3,9 +3,24 @@
 mod setup;
 mod utils;
 
-use crate::setup::setup;
-use crate::utils::{bob_balance, join_native_pool, mine_block, spawn_miner, upgrade_miner};
+use crate::setup::{get_miner_wasm, setup, upgrade_bob};
+use crate::utils::{
+    approve_icp, bob_balance, bob_total_supply, call_bob_with_cycles, commit_miner_wasm,
+    get_challenge, get_config, get_emission_info, get_miner_refill_log, get_miner_rollout,
+    get_miner_state, get_miner_statistics, get_miner_wasm_versions, get_miners, get_round_history,
+    get_submission_metrics, hours_left_in_pool, join_native_pool, join_pool_with_icrc2, mine_block,
+    set_miner_auto_top_up, set_miner_operator, spawn_miner, spawn_miner_with_icrc2, spawn_miners,
+    start_miner_rollout, submit_burned_cycles, try_update_config, update_config,
+    update_miner_settings, upgrade_miner, upload_miner_wasm_chunk, wait_for_upgraded_miners,
+};
+use bob_miner_v2::{MinerSettings, OperatorPermission};
+use bob_minter_v2::{
+    sha256_hex, AutoTopUp, BlockSelection, Config, MinerRolloutArgs, Payment, BLOCK_HALVING,
+    COINBASE_REWARDS, CYCLES_FOR_CREATION, CYCLES_PER_POOL_DAY, HISTORICAL_BLOCKS,
+};
 use candid::Principal;
+use pocket_ic::update_candid_as;
+use std::time::Duration;
 
 // System canister IDs
 
@@ -20,6 +35,12 @@ pub(crate) const NNS_CYCLES_MINTING_CANISTER_ID: Principal =
 pub(crate) const NNS_ICP_INDEX_CANISTER_ID: Principal =
     Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 0xB, 1, 1]);
 
+// Test canister IDs
+
+// Forwards calls with cycles attached, see `bob/cycles-proxy`.
+pub(crate) const CYCLES_PROXY_CANISTER_ID: Principal =
+    Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 0x64, 1, 1]);
+
 // BoB canister IDs
 
 pub(crate) const BOB_CANISTER_ID: Principal =
@@ -35,6 +56,7 @@ fn test_spawn_upgrade_miner() {
     let pic = setup(vec![user_id]);
 
     let miner_id = spawn_miner(&pic, user_id, 100_000_000);
+    assert_eq!(pic.get_controllers(miner_id), vec![BOB_CANISTER_ID]);
 
     assert_eq!(bob_balance(&pic, user_id), 0_u64);
     mine_block(&pic);
@@ -42,12 +64,36 @@ fn test_spawn_upgrade_miner() {
     mine_block(&pic);
     assert_eq!(bob_balance(&pic, user_id), 120_000_000_000_u64);
 
+    update_miner_settings(
+        &pic,
+        user_id,
+        miner_id,
+        MinerSettings {
+            max_cycles_per_round: Some(20_000_000_000),
+            new_owner: None,
+            round_length_secs: Some(300),
+            target_daily_burn: None,
+            reserve_cycles: None,
+            active_hours: None,
+        },
+    );
+
     let miner_cycles_before_upgrade = pic.cycle_balance(miner_id);
     upgrade_miner(&pic, user_id, miner_id);
     let miner_cycles = pic.cycle_balance(miner_id);
     let upgrade_cycles = miner_cycles_before_upgrade - miner_cycles;
     assert!(upgrade_cycles <= 3_000_000_000);
 
+    // the miner settings survive the upgrade
+    let stats = get_miner_statistics(&pic, miner_id);
+    assert_eq!(stats.cycles_burned_per_round, 20_000_000_000);
+    assert_eq!(stats.round_length_secs, 300);
+    // 288 rounds of 300 seconds per day, above the reserve of 100B cycles
+    assert_eq!(
+        stats.runway_days,
+        Some(((stats.cycle_balance as u128 - 100_000_000_000) / (20_000_000_000 * 288)) as u64)
+    );
+
     assert_eq!(bob_balance(&pic, user_id), 120_000_000_000_u64);
     mine_block(&pic);
     assert_eq!(bob_balance(&pic, user_id), 180_000_000_000_u64);
@@ -55,6 +101,340 @@ fn test_spawn_upgrade_miner() {
     assert_eq!(bob_balance(&pic, user_id), 240_000_000_000_u64);
 }
 
+#[test]
+fn test_miner_operator() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let operator_id = Principal::from_slice(&[0xFE; 29]);
+    let pic = setup(vec![user_id]);
+
+    let miner_id = spawn_miner(&pic, user_id, 100_000_000);
+    set_miner_operator(
+        &pic,
+        user_id,
+        miner_id,
+        operator_id,
+        vec![
+            OperatorPermission::AdjustBurnRate,
+            OperatorPermission::Upgrade,
+        ],
+    );
+
+    update_miner_settings(
+        &pic,
+        operator_id,
+        miner_id,
+        MinerSettings {
+            max_cycles_per_round: Some(20_000_000_000),
+            ..Default::default()
+        },
+    );
+    upgrade_miner(&pic, operator_id, miner_id);
+    assert_eq!(
+        get_miner_statistics(&pic, miner_id).cycles_burned_per_round,
+        20_000_000_000
+    );
+
+    // operators can neither take over the miner nor use permissions they lack
+    assert!(update_candid_as::<_, ((),)>(
+        &pic,
+        miner_id,
+        operator_id,
+        "update_miner_settings",
+        (MinerSettings {
+            new_owner: Some(operator_id),
+            ..Default::default()
+        },),
+    )
+    .is_err());
+    let auto_top_up = AutoTopUp {
+        threshold_cycles: 1_000_000_000_000,
+        amount_e8s: 10_000_000,
+    };
+    assert!(update_candid_as::<_, (Result<(), String>,)>(
+        &pic,
+        BOB_CANISTER_ID,
+        operator_id,
+        "set_miner_auto_top_up",
+        (miner_id, Some(auto_top_up)),
+    )
+    .unwrap()
+    .0
+    .is_err());
+
+    // revoking the operator removes all its permissions
+    set_miner_operator(&pic, user_id, miner_id, operator_id, vec![]);
+    assert!(get_miner_state(&pic, miner_id).operators.is_empty());
+    assert!(update_candid_as::<_, (Result<(), String>,)>(
+        &pic,
+        BOB_CANISTER_ID,
+        operator_id,
+        "upgrade_miner",
+        (miner_id,),
+    )
+    .unwrap()
+    .0
+    .is_err());
+}
+
+#[test]
+fn test_miner_round_history() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let pic = setup(vec![user_id]);
+
+    let miner_id = spawn_miner(&pic, user_id, 100_000_000);
+    mine_block(&pic);
+    mine_block(&pic);
+
+    let rounds = get_round_history(&pic, miner_id, 0, 100);
+    assert!(!rounds.is_empty());
+    assert!(rounds.iter().all(|round| round.result.is_ok()));
+    assert!(rounds.windows(2).all(|w| w[0].timestamp >= w[1].timestamp));
+    assert_eq!(
+        get_round_history(&pic, miner_id, 1, 100).len(),
+        rounds.len() - 1
+    );
+
+    // the minter notifies the miner once the block rewards are transferred
+    pic.tick();
+    pic.tick();
+    let state = get_miner_state(&pic, miner_id);
+    assert_eq!(state.solved_challenges, 2);
+    assert_eq!(state.rewards_won, 120_000_000_000);
+}
+
+#[test]
+fn test_auto_top_up_miner() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let pic = setup(vec![user_id]);
+
+    let miner_id = spawn_miner(&pic, user_id, 100_000_000);
+    let miner_cycles = pic.cycle_balance(miner_id);
+
+    approve_icp(&pic, user_id, BOB_CANISTER_ID, 100_000_000);
+    set_miner_auto_top_up(
+        &pic,
+        user_id,
+        miner_id,
+        Some(AutoTopUp {
+            threshold_cycles: u128::MAX,
+            amount_e8s: 50_000_000,
+        }),
+    );
+
+    while get_miner_refill_log(&pic, miner_id).is_empty() {
+        pic.advance_time(std::time::Duration::from_secs(1));
+        pic.tick();
+    }
+
+    let refills = get_miner_refill_log(&pic, miner_id);
+    assert_eq!(refills.len(), 1);
+    assert_eq!(refills[0].amount_e8s, 50_000_000);
+    assert!(refills[0].result.is_ok(), "{:?}", refills[0].result);
+    assert!(pic.cycle_balance(miner_id) > miner_cycles);
+}
+
+#[test]
+fn test_hash_challenge_mode() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let pic = setup(vec![user_id]);
+
+    let mut config = get_config(&pic);
+    config.block_selection = BlockSelection::HashChallenge;
+    config.challenge_difficulty = 8;
+    update_config(&pic, config);
+
+    // The native pool cannot solve challenges.
+    assert!(join_pool_with_icrc2(&pic, user_id, 100_000_000)
+        .unwrap_err()
+        .contains("hash challenge mode"));
+
+    let miner_id = spawn_miner(&pic, user_id, 100_000_000);
+
+    mine_block(&pic);
+    assert_eq!(bob_balance(&pic, user_id), 60_000_000_000_u64);
+    assert!(get_miner_state(&pic, miner_id).hashes_computed > 0);
+
+    // The open challenge survives an upgrade of the minter.
+    while get_challenge(&pic).is_none() {
+        pic.advance_time(Duration::from_secs(1));
+        pic.tick();
+    }
+    let challenge = get_challenge(&pic);
+    upgrade_bob(&pic);
+    assert_eq!(get_challenge(&pic), challenge);
+}
+
+#[test]
+fn test_spawn_miner_with_icrc2() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let pic = setup(vec![user_id]);
+
+    spawn_miner_with_icrc2(&pic, user_id, 100_000_000);
+
+    assert_eq!(bob_balance(&pic, user_id), 0_u64);
+    mine_block(&pic);
+    assert_eq!(bob_balance(&pic, user_id), 60_000_000_000_u64);
+}
+
+#[test]
+fn test_pay_with_cycles() {
+    let pic = setup(vec![]);
+    let price = CYCLES_FOR_CREATION as u128;
+
+    let (result, refunded): (Result<Principal, String>, u128) =
+        call_bob_with_cycles(&pic, "spawn_miner_with_payment", Payment::Cycles, price - 1);
+    assert!(result.unwrap_err().contains("not enough cycles"));
+    assert_eq!(refunded, price - 1);
+
+    let (result, refunded): (Result<Principal, String>, u128) = call_bob_with_cycles(
+        &pic,
+        "spawn_miner_with_payment",
+        Payment::Cycles,
+        price + 1_000,
+    );
+    result.unwrap();
+    assert_eq!(refunded, 1_000);
+    mine_block(&pic);
+    assert_eq!(
+        bob_balance(&pic, CYCLES_PROXY_CANISTER_ID),
+        60_000_000_000_u64
+    );
+
+    let (result, refunded): (Result<(), String>, u128) = call_bob_with_cycles(
+        &pic,
+        "join_pool_with_payment",
+        Payment::Cycles,
+        CYCLES_PER_POOL_DAY - 1,
+    );
+    assert!(result.unwrap_err().contains("not enough cycles"));
+    assert_eq!(refunded, CYCLES_PER_POOL_DAY - 1);
+
+    let (result, refunded): (Result<(), String>, u128) = call_bob_with_cycles(
+        &pic,
+        "join_pool_with_payment",
+        Payment::Cycles,
+        2 * CYCLES_PER_POOL_DAY + 1_000,
+    );
+    result.unwrap();
+    assert_eq!(refunded, 1_000);
+    assert!((47..=48).contains(&hours_left_in_pool(&pic, CYCLES_PROXY_CANISTER_ID)));
+}
+
+#[test]
+fn test_join_pool_with_icrc2() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let pic = setup(vec![user_id]);
+
+    assert!(join_pool_with_icrc2(&pic, user_id, 10_000_000)
+        .unwrap_err()
+        .contains("amount too low"));
+
+    join_pool_with_icrc2(&pic, user_id, 200_000_000).unwrap();
+    assert!((47..=48).contains(&hours_left_in_pool(&pic, user_id)));
+
+    mine_block(&pic);
+    assert_eq!(bob_balance(&pic, user_id), 60_000_000_000_u64);
+}
+
+#[test]
+fn test_spawn_miners() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let pic = setup(vec![user_id]);
+
+    let miners: Vec<Principal> = spawn_miners(&pic, user_id, 300_000_000, 3)
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect();
+    assert_eq!(miners.len(), 3);
+    assert!(miners[0] != miners[1] && miners[1] != miners[2]);
+
+    mine_block(&pic);
+    assert_eq!(bob_balance(&pic, user_id), 60_000_000_000_u64);
+}
+
+#[test]
+fn test_miner_wasm_rollout() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let pic = setup(vec![user_id]);
+
+    let miners: Vec<Principal> = spawn_miners(&pic, user_id, 300_000_000, 3)
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect();
+
+    let wasm = get_miner_wasm();
+    let sha256 = sha256_hex(&wasm);
+    let (first_chunk, second_chunk) = wasm.split_at(wasm.len() / 2);
+
+    assert!(upload_miner_wasm_chunk(&pic, user_id, 1, first_chunk.to_vec()).is_err());
+    assert_eq!(
+        upload_miner_wasm_chunk(&pic, NNS_ROOT_CANISTER_ID, 1, first_chunk.to_vec()),
+        Ok(first_chunk.len() as u64)
+    );
+    assert!(commit_miner_wasm(&pic, 1, sha256.clone())
+        .unwrap_err()
+        .contains("sha256 mismatch"));
+    assert_eq!(
+        upload_miner_wasm_chunk(&pic, NNS_ROOT_CANISTER_ID, 1, second_chunk.to_vec()),
+        Ok(wasm.len() as u64)
+    );
+    assert!(commit_miner_wasm(&pic, 1, "00".repeat(32))
+        .unwrap_err()
+        .contains("sha256 mismatch"));
+    assert_eq!(commit_miner_wasm(&pic, 1, sha256.clone()), Ok(()));
+    assert!(upload_miner_wasm_chunk(&pic, NNS_ROOT_CANISTER_ID, 1, vec![0]).is_err());
+    assert!(commit_miner_wasm(&pic, 2, sha256.clone())
+        .unwrap_err()
+        .contains("no upload"));
+
+    let versions = get_miner_wasm_versions(&pic);
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].version, 0);
+    assert_eq!(versions[1].version, 1);
+    assert_eq!(versions[1].sha256, sha256);
+    assert_eq!(versions[1].size, wasm.len() as u64);
+
+    let args = MinerRolloutArgs {
+        version: 1,
+        wave_size: 2,
+        max_failures: 0,
+    };
+    assert!(start_miner_rollout(
+        &pic,
+        MinerRolloutArgs {
+            version: 3,
+            ..args.clone()
+        }
+    )
+    .is_err());
+    assert_eq!(start_miner_rollout(&pic, args), Ok(()));
+
+    // The first wave upgrades two miners, the last one waits for the next wave.
+    let rollout = wait_for_upgraded_miners(&pic, 2);
+    assert_eq!(rollout.upgraded, 2);
+    assert!(rollout.failed.is_empty());
+    assert_eq!(rollout.completed_at, None);
+
+    // The rollout resumes after an upgrade of the minter.
+    upgrade_bob(&pic);
+    let rollout = wait_for_upgraded_miners(&pic, 3);
+    assert!(rollout.failed.is_empty());
+    for _ in 0..10 {
+        pic.advance_time(Duration::from_secs(60));
+        pic.tick();
+    }
+    let rollout = get_miner_rollout(&pic).unwrap();
+    assert_eq!(rollout.upgraded, 3);
+    assert!(rollout.completed_at.is_some());
+
+    let upgraded: Vec<Principal> = get_miners(&pic, user_id)
+        .into_iter()
+        .filter(|miner| miner.wasm_version == 1)
+        .map(|miner| miner.id)
+        .collect();
+    assert_eq!(upgraded, miners);
+}
+
 #[test]
 fn test_native_pool() {
     let user_1 = Principal::from_slice(&[0xFF; 29]);
@@ -70,3 +450,113 @@ fn test_native_pool() {
     assert_eq!(bob_balance(&pic, user_1), 30_000_000_000_u64);
     assert_eq!(bob_balance(&pic, user_2), 30_000_000_000_u64);
 }
+
+#[test]
+fn test_update_config_validation() {
+    let pic = setup(vec![]);
+    let config = get_config(&pic);
+
+    for invalid_config in [
+        Config {
+            miner_owner_is_controller: true,
+            accept_unverified_burns: true,
+            ..config.clone()
+        },
+        Config {
+            max_submissions_per_round: 0,
+            ..config.clone()
+        },
+        Config {
+            challenge_difficulty: 257,
+            ..config.clone()
+        },
+    ] {
+        assert!(try_update_config(&pic, invalid_config).is_err());
+    }
+    assert_eq!(get_config(&pic), config);
+
+    try_update_config(
+        &pic,
+        Config {
+            miner_owner_is_controller: true,
+            accept_unverified_burns: false,
+            challenge_difficulty: 256,
+            ..config
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_submission_rate_limit() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let pic = setup(vec![user_id]);
+
+    let mut config = get_config(&pic);
+    config.max_submissions_per_round = 2;
+    config.min_submission_interval_secs = 30;
+    update_config(&pic, config);
+
+    let miner_id = spawn_miner(&pic, user_id, 100_000_000);
+
+    // only miners may submit, other ingress messages are dropped
+    assert!(update_candid_as::<_, (Result<(), String>,)>(
+        &pic,
+        BOB_CANISTER_ID,
+        user_id,
+        "submit_burned_cycles",
+        (10_000_000_000_u64,),
+    )
+    .is_err());
+
+    assert!(submit_burned_cycles(&pic, miner_id, 1_000).is_err());
+    assert_eq!(submit_burned_cycles(&pic, miner_id, 10_000_000_000), Ok(()));
+    let too_early = submit_burned_cycles(&pic, miner_id, 10_000_000_000).unwrap_err();
+    assert!(too_early.contains("too early"), "{too_early}");
+    pic.advance_time(Duration::from_secs(29));
+    assert!(submit_burned_cycles(&pic, miner_id, 10_000_000_000).is_err());
+    pic.advance_time(Duration::from_secs(1));
+    assert_eq!(submit_burned_cycles(&pic, miner_id, 10_000_000_000), Ok(()));
+    pic.advance_time(Duration::from_secs(30));
+    let too_many = submit_burned_cycles(&pic, miner_id, 10_000_000_000).unwrap_err();
+    assert!(too_many.contains("Too many submissions"), "{too_many}");
+
+    let metrics = get_submission_metrics(&pic);
+    assert_eq!(metrics.accepted, 2);
+    assert_eq!(metrics.rejected_rate_limited, 3);
+    assert_eq!(metrics.rejected_not_enough_cycles, 1);
+    assert_eq!(metrics.rejected_unregistered, 0);
+
+    // the metrics survive an upgrade of the minter
+    upgrade_bob(&pic);
+    let metrics = get_submission_metrics(&pic);
+    assert_eq!(metrics.accepted, 2);
+    assert_eq!(metrics.rejected_rate_limited, 3);
+}
+
+#[test]
+fn test_emission_info() {
+    let user_id = Principal::from_slice(&[0xFF; 29]);
+    let pic = setup(vec![user_id]);
+
+    spawn_miner(&pic, user_id, 100_000_000);
+    mine_block(&pic);
+    mine_block(&pic);
+
+    let info = get_emission_info(&pic);
+    assert_eq!(info.current_epoch, 0);
+    assert_eq!(info.current_rewards, COINBASE_REWARDS);
+    assert_eq!(
+        info.blocks_until_next_halving,
+        BLOCK_HALVING - HISTORICAL_BLOCKS - 2
+    );
+    assert_eq!(info.max_supply, 2_099_999_999_737_500_u64);
+    assert!(info.estimated_next_halving_ts.is_some());
+
+    // Historical blocks were minted on the mainnet ledger only.
+    let historical_supply = HISTORICAL_BLOCKS * COINBASE_REWARDS;
+    assert_eq!(
+        info.emitted_supply - historical_supply,
+        bob_total_supply(&pic)
+    );
+}
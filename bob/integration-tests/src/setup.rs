@@ -1,7 +1,7 @@
 use crate::{
-    BOB_CANISTER_ID, BOB_LEDGER_CANISTER_ID, NNS_CYCLES_MINTING_CANISTER_ID,
-    NNS_GOVERNANCE_CANISTER_ID, NNS_ICP_INDEX_CANISTER_ID, NNS_ICP_LEDGER_CANISTER_ID,
-    NNS_ROOT_CANISTER_ID,
+    BOB_CANISTER_ID, BOB_LEDGER_CANISTER_ID, CYCLES_PROXY_CANISTER_ID,
+    NNS_CYCLES_MINTING_CANISTER_ID, NNS_GOVERNANCE_CANISTER_ID, NNS_ICP_INDEX_CANISTER_ID,
+    NNS_ICP_LEDGER_CANISTER_ID, NNS_ROOT_CANISTER_ID,
 };
 use candid::{CandidType, Encode, Principal};
 use ic_icrc1_ledger::{InitArgsBuilder, LedgerArgument};
@@ -30,6 +30,12 @@ struct NnsLedgerCanisterInitPayload {
     pub transfer_fee: Option<Tokens>,
     pub token_symbol: Option<String>,
     pub token_name: Option<String>,
+    pub feature_flags: Option<FeatureFlags>,
+}
+
+#[derive(CandidType)]
+struct FeatureFlags {
+    pub icrc2: bool,
 }
 
 #[derive(CandidType)]
@@ -57,6 +63,10 @@ fn get_canister_wasm(canister_name: &str) -> Vec<u8> {
     read_file_from_local_bin(&format!("{canister_name}.wasm.gz"))
 }
 
+pub(crate) fn get_miner_wasm() -> Vec<u8> {
+    get_canister_wasm("bob_miner_v2")
+}
+
 fn local_bin() -> PathBuf {
     let mut file_path = PathBuf::from(
         std::env::var("CARGO_MANIFEST_DIR")
@@ -101,6 +111,7 @@ fn deploy_icp_ledger_canister(pic: &PocketIc, icp_holders: Vec<Principal>) {
         transfer_fee: Some(Tokens::from_e8s(10_000)),
         token_symbol: Some("ICP".to_string()),
         token_name: Some("Internet Computer".to_string()),
+        feature_flags: Some(FeatureFlags { icrc2: true }),
     });
     pic.install_canister(
         icp_ledger_canister_id,
@@ -194,6 +205,16 @@ fn deploy_bob(pic: &PocketIc) {
     );
 }
 
+pub(crate) fn upgrade_bob(pic: &PocketIc) {
+    pic.upgrade_canister(
+        BOB_CANISTER_ID,
+        get_canister_wasm("bob-minter-v2").to_vec(),
+        Encode!(&()).unwrap(),
+        Some(NNS_ROOT_CANISTER_ID),
+    )
+    .unwrap();
+}
+
 fn deploy_bob_ledger(pic: &PocketIc) {
     let bob_ledger_canister_id = pic
         .create_canister_with_id(Some(NNS_ROOT_CANISTER_ID), None, BOB_LEDGER_CANISTER_ID)
@@ -224,12 +245,27 @@ fn deploy_bob_canisters(pic: &PocketIc) {
     deploy_bob_ledger(pic);
 }
 
+fn deploy_cycles_proxy(pic: &PocketIc) {
+    let cycles_proxy_canister_id = pic
+        .create_canister_with_id(Some(NNS_ROOT_CANISTER_ID), None, CYCLES_PROXY_CANISTER_ID)
+        .unwrap();
+    assert_eq!(cycles_proxy_canister_id, CYCLES_PROXY_CANISTER_ID);
+    pic.add_cycles(cycles_proxy_canister_id, 100_000_000_000_000);
+    pic.install_canister(
+        cycles_proxy_canister_id,
+        get_canister_wasm("cycles_proxy").to_vec(),
+        Encode!(&()).unwrap(),
+        Some(NNS_ROOT_CANISTER_ID),
+    );
+}
+
 pub(crate) fn setup(icp_holders: Vec<Principal>) -> PocketIc {
     let pic = PocketIcBuilder::new().with_nns_subnet().build();
     pic.set_time(SystemTime::now());
 
     deploy_system_canisters(&pic, icp_holders);
     deploy_bob_canisters(&pic);
+    deploy_cycles_proxy(&pic);
 
     pic
 }
@@ -1,11 +1,17 @@
 use crate::{
-    BOB_CANISTER_ID, BOB_LEDGER_CANISTER_ID, NNS_ICP_INDEX_CANISTER_ID, NNS_ICP_LEDGER_CANISTER_ID,
+    BOB_CANISTER_ID, BOB_LEDGER_CANISTER_ID, CYCLES_PROXY_CANISTER_ID, NNS_ICP_INDEX_CANISTER_ID,
+    NNS_ICP_LEDGER_CANISTER_ID, NNS_ROOT_CANISTER_ID,
 };
-use bob_minter_v2::Stats;
-use candid::{Nat, Principal};
+use bob_miner_v2::{MinerSettings, OperatorPermission, Round, State, StatsV2};
+use bob_minter_v2::{
+    AutoTopUp, Challenge, Config, EmissionInfo, Miner, MinerRefill, MinerRollout, MinerRolloutArgs,
+    MinerWasmVersion, Payment, Stats, SubmissionMetrics,
+};
+use candid::{CandidType, Deserialize, Nat, Principal};
 use ic_ledger_core::block::BlockType;
 use ic_ledger_types::{AccountIdentifier, Memo, Tokens, TransferArgs, TransferResult};
 use icrc_ledger_types::icrc1::account::Account;
+use icrc_ledger_types::icrc2::approve::{ApproveArgs, ApproveError};
 use pocket_ic::{update_candid_as, PocketIc};
 
 pub(crate) fn get_icp_block(pic: &PocketIc, block_index: u64) -> Option<icp_ledger::Block> {
@@ -75,6 +81,124 @@ pub(crate) fn spawn_miner(pic: &PocketIc, user_id: Principal, amount: u64) -> Pr
     .unwrap()
 }
 
+pub(crate) fn spawn_miners(
+    pic: &PocketIc,
+    user_id: Principal,
+    amount: u64,
+    count: u64,
+) -> Vec<Result<Principal, String>> {
+    let block_index = transfer(pic, user_id, amount);
+
+    update_candid_as::<_, (Result<Vec<Result<Principal, String>>, String>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        user_id,
+        "spawn_miners",
+        (block_index, count),
+    )
+    .unwrap()
+    .0
+    .unwrap()
+}
+
+pub(crate) fn approve_icp(pic: &PocketIc, user_id: Principal, spender: Principal, amount: u64) {
+    let approve_args = ApproveArgs {
+        from_subaccount: None,
+        spender: spender.into(),
+        amount: Nat::from(amount),
+        expected_allowance: None,
+        expires_at: None,
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+    update_candid_as::<_, (Result<Nat, ApproveError>,)>(
+        pic,
+        NNS_ICP_LEDGER_CANISTER_ID,
+        user_id,
+        "icrc2_approve",
+        (approve_args,),
+    )
+    .unwrap()
+    .0
+    .unwrap();
+}
+
+pub(crate) fn spawn_miner_with_icrc2(pic: &PocketIc, user_id: Principal, amount: u64) -> Principal {
+    approve_icp(pic, user_id, BOB_CANISTER_ID, amount + 10_000);
+
+    update_candid_as::<_, (Result<Principal, String>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        user_id,
+        "spawn_miner_with_payment",
+        (Payment::Icrc2 { amount },),
+    )
+    .unwrap()
+    .0
+    .unwrap()
+}
+
+pub(crate) fn join_pool_with_icrc2(
+    pic: &PocketIc,
+    user_id: Principal,
+    amount: u64,
+) -> Result<(), String> {
+    approve_icp(pic, user_id, BOB_CANISTER_ID, amount + 10_000);
+
+    update_candid_as::<_, (Result<(), String>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        user_id,
+        "join_pool_with_payment",
+        (Payment::Icrc2 { amount },),
+    )
+    .unwrap()
+    .0
+}
+
+/// Calls the minter from the cycles proxy with the cycles attached, returns
+/// the reply along with the cycles refunded to the proxy.
+pub(crate) fn call_bob_with_cycles<A, R>(
+    pic: &PocketIc,
+    method: &str,
+    arg: A,
+    cycles: u128,
+) -> (R, u128)
+where
+    A: CandidType,
+    R: CandidType + for<'de> Deserialize<'de>,
+{
+    let (reply, refunded) = update_candid_as::<_, (Result<(Vec<u8>, u128), String>,)>(
+        pic,
+        CYCLES_PROXY_CANISTER_ID,
+        Principal::anonymous(),
+        "call_with_cycles",
+        (
+            BOB_CANISTER_ID,
+            method.to_string(),
+            candid::encode_one(arg).unwrap(),
+            cycles,
+        ),
+    )
+    .unwrap()
+    .0
+    .unwrap();
+    (candid::decode_one(&reply).unwrap(), refunded)
+}
+
+pub(crate) fn hours_left_in_pool(pic: &PocketIc, user_id: Principal) -> u64 {
+    update_candid_as::<_, (u64,)>(
+        pic,
+        BOB_CANISTER_ID,
+        Principal::anonymous(),
+        "hours_left_in_pool",
+        (Some(user_id),),
+    )
+    .unwrap()
+    .0
+}
+
 pub(crate) fn upgrade_miner(pic: &PocketIc, user_id: Principal, miner_id: Principal) {
     update_candid_as::<_, (Result<(), String>,)>(
         pic,
@@ -88,6 +212,192 @@ pub(crate) fn upgrade_miner(pic: &PocketIc, user_id: Principal, miner_id: Princi
     .unwrap()
 }
 
+pub(crate) fn upload_miner_wasm_chunk(
+    pic: &PocketIc,
+    user_id: Principal,
+    version: u64,
+    chunk: Vec<u8>,
+) -> Result<u64, String> {
+    update_candid_as::<_, (Result<u64, String>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        user_id,
+        "upload_miner_wasm_chunk",
+        (version, chunk),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn commit_miner_wasm(
+    pic: &PocketIc,
+    version: u64,
+    sha256: String,
+) -> Result<(), String> {
+    update_candid_as::<_, (Result<(), String>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        NNS_ROOT_CANISTER_ID,
+        "commit_miner_wasm",
+        (version, sha256),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn get_miner_wasm_versions(pic: &PocketIc) -> Vec<MinerWasmVersion> {
+    update_candid_as::<_, (Vec<MinerWasmVersion>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        Principal::anonymous(),
+        "get_miner_wasm_versions",
+        (),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn start_miner_rollout(pic: &PocketIc, args: MinerRolloutArgs) -> Result<(), String> {
+    update_candid_as::<_, (Result<(), String>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        NNS_ROOT_CANISTER_ID,
+        "start_miner_rollout",
+        (args,),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn get_miner_rollout(pic: &PocketIc) -> Option<MinerRollout> {
+    update_candid_as::<_, (Option<MinerRollout>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        Principal::anonymous(),
+        "get_miner_rollout",
+        (),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn get_miners(pic: &PocketIc, user_id: Principal) -> Vec<Miner> {
+    update_candid_as::<_, (Vec<Miner>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        Principal::anonymous(),
+        "get_miners",
+        (user_id,),
+    )
+    .unwrap()
+    .0
+}
+
+/// Waits until the rollout upgraded the given number of miners.
+pub(crate) fn wait_for_upgraded_miners(pic: &PocketIc, upgraded: u64) -> MinerRollout {
+    for _ in 0..100 {
+        let rollout = get_miner_rollout(pic).unwrap();
+        if rollout.upgraded >= upgraded {
+            return rollout;
+        }
+        pic.advance_time(std::time::Duration::from_secs(1));
+        pic.tick();
+    }
+    panic!("the rollout did not upgrade {upgraded} miners");
+}
+
+pub(crate) fn update_miner_settings(
+    pic: &PocketIc,
+    user_id: Principal,
+    miner_id: Principal,
+    settings: MinerSettings,
+) {
+    update_candid_as::<_, ((),)>(pic, miner_id, user_id, "update_miner_settings", (settings,))
+        .unwrap()
+}
+
+pub(crate) fn set_miner_operator(
+    pic: &PocketIc,
+    user_id: Principal,
+    miner_id: Principal,
+    operator: Principal,
+    permissions: Vec<OperatorPermission>,
+) {
+    update_candid_as::<_, ((),)>(
+        pic,
+        miner_id,
+        user_id,
+        "set_operator",
+        (operator, permissions),
+    )
+    .unwrap()
+}
+
+pub(crate) fn get_miner_statistics(pic: &PocketIc, miner_id: Principal) -> StatsV2 {
+    update_candid_as::<_, (StatsV2,)>(
+        pic,
+        miner_id,
+        Principal::anonymous(),
+        "get_statistics_v2",
+        ((),),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn get_miner_state(pic: &PocketIc, miner_id: Principal) -> State {
+    update_candid_as::<_, (State,)>(pic, miner_id, Principal::anonymous(), "get_state", ((),))
+        .unwrap()
+        .0
+}
+
+pub(crate) fn get_round_history(
+    pic: &PocketIc,
+    miner_id: Principal,
+    offset: u64,
+    length: u64,
+) -> Vec<Round> {
+    update_candid_as::<_, (Vec<Round>,)>(
+        pic,
+        miner_id,
+        Principal::anonymous(),
+        "get_round_history",
+        (offset, length),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn set_miner_auto_top_up(
+    pic: &PocketIc,
+    user_id: Principal,
+    miner_id: Principal,
+    auto_top_up: Option<AutoTopUp>,
+) {
+    update_candid_as::<_, (Result<(), String>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        user_id,
+        "set_miner_auto_top_up",
+        (miner_id, auto_top_up),
+    )
+    .unwrap()
+    .0
+    .unwrap()
+}
+
+pub(crate) fn get_miner_refill_log(pic: &PocketIc, miner_id: Principal) -> Vec<MinerRefill> {
+    update_candid_as::<_, (Vec<MinerRefill>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        Principal::anonymous(),
+        "get_miner_refill_log",
+        (miner_id,),
+    )
+    .unwrap()
+    .0
+}
+
 pub(crate) fn join_native_pool(pic: &PocketIc, user_id: Principal, amount: u64) {
     let block_index = transfer(pic, user_id, amount);
 
@@ -115,6 +425,87 @@ pub(crate) fn get_stats(pic: &PocketIc) -> Stats {
     .0
 }
 
+pub(crate) fn get_config(pic: &PocketIc) -> Config {
+    update_candid_as::<_, (Config,)>(
+        pic,
+        BOB_CANISTER_ID,
+        Principal::anonymous(),
+        "get_config",
+        ((),),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn update_config(pic: &PocketIc, config: Config) {
+    try_update_config(pic, config).unwrap()
+}
+
+pub(crate) fn try_update_config(pic: &PocketIc, config: Config) -> Result<(), String> {
+    update_candid_as::<_, (Result<(), String>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        NNS_ROOT_CANISTER_ID,
+        "update_config",
+        (config,),
+    )
+    .unwrap()
+    .0
+}
+
+/// Submits as the miner would, without attaching the burned cycles.
+pub(crate) fn submit_burned_cycles(
+    pic: &PocketIc,
+    miner_id: Principal,
+    cycles: u64,
+) -> Result<(), String> {
+    update_candid_as::<_, (Result<(), String>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        miner_id,
+        "submit_burned_cycles",
+        (cycles,),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn get_submission_metrics(pic: &PocketIc) -> SubmissionMetrics {
+    update_candid_as::<_, (SubmissionMetrics,)>(
+        pic,
+        BOB_CANISTER_ID,
+        Principal::anonymous(),
+        "get_submission_metrics",
+        ((),),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn get_challenge(pic: &PocketIc) -> Option<Challenge> {
+    update_candid_as::<_, (Option<Challenge>,)>(
+        pic,
+        BOB_CANISTER_ID,
+        Principal::anonymous(),
+        "get_challenge",
+        (),
+    )
+    .unwrap()
+    .0
+}
+
+pub(crate) fn get_emission_info(pic: &PocketIc) -> EmissionInfo {
+    update_candid_as::<_, (EmissionInfo,)>(
+        pic,
+        BOB_CANISTER_ID,
+        Principal::anonymous(),
+        "get_emission_info",
+        ((),),
+    )
+    .unwrap()
+    .0
+}
+
 pub(crate) fn mine_block(pic: &PocketIc) {
     let old_stats = get_stats(pic);
 
@@ -149,3 +540,18 @@ pub(crate) fn bob_balance(pic: &PocketIc, user_id: Principal) -> u64 {
     .try_into()
     .unwrap()
 }
+
+pub(crate) fn bob_total_supply(pic: &PocketIc) -> u64 {
+    update_candid_as::<_, (Nat,)>(
+        pic,
+        BOB_LEDGER_CANISTER_ID,
+        Principal::anonymous(),
+        "icrc1_total_supply",
+        ((),),
+    )
+    .unwrap()
+    .0
+     .0
+    .try_into()
+    .unwrap()
+}
@@ -1,74 +1,196 @@
-use candid::{CandidType, Principal};
-use std::cell::RefCell;
+use crate::memory::push_round;
+use crate::state::StateExt;
+use candid::Principal;
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
 
-const DEFAULT_BURNED_CYCLES_PER_ROUND: u128 = 10_000_000_001;
+pub mod memory;
+pub mod state;
+
+pub use bob_types::miner::*;
+
+// The minter rejects submissions of fewer cycles.
+const MIN_BURNED_CYCLES_PER_ROUND: u128 = 1_000_000_000;
+
+// Number of rounds kept in the round history, the oldest are dropped first.
+const MAX_ROUND_HISTORY: usize = 2_000;
+
+// Instructions spent searching nonces in a single message.
+const SEARCH_INSTRUCTION_LIMIT: u64 = 5_000_000_000;
 
 pub async fn process_logic() {
-    let max_cycles_per_round = read_state(|s| s.max_cycles_per_round);
+    let now_secs = ic_cdk::api::time() / 1_000_000_000;
+    let (is_active, cycles_per_round, reserve_cycles) = read_state(|s| {
+        (
+            s.is_active_at(now_secs),
+            s.cycles_per_round(),
+            s.reserve_cycles,
+        )
+    });
+
+    let cycles_to_burn =
+        cycles_per_round.min(ic_cdk::api::canister_balance128().saturating_sub(reserve_cycles));
 
-    if max_cycles_per_round < DEFAULT_BURNED_CYCLES_PER_ROUND {
+    // Cycles are spent searching nonces while the minter runs challenges.
+    let has_challenge = read_state(|s| s.active_challenge(ic_cdk::api::time()).is_some());
+
+    if !is_active || has_challenge || cycles_to_burn < MIN_BURNED_CYCLES_PER_ROUND {
         mutate_state(|s| {
             s.last_cycles_burned = 0;
         });
         return;
     }
 
-    let burned_cycles = ic_cdk::api::cycles_burn(max_cycles_per_round);
+    let (burned_cycles, result) = submit_burned_cycles(cycles_to_burn).await;
     mutate_state(|s| {
         s.last_cycles_burned = burned_cycles;
     });
-
-    if burned_cycles < DEFAULT_BURNED_CYCLES_PER_ROUND {
-        return;
-    }
-
-    let _ = submit_burned_cycles(burned_cycles as u64).await;
+    push_round(Round {
+        timestamp: ic_cdk::api::time(),
+        cycles_burned: burned_cycles,
+        result,
+    });
 }
 
-async fn submit_burned_cycles(cycles: u64) -> Result<(), String> {
+/// Attaches the cycles to burn to the submission, the minter burns
+/// whatever it accepts. Returns the number of cycles burned along with the
+/// outcome of the submission.
+async fn submit_burned_cycles(cycles: u128) -> (u128, Result<(), String>) {
     let bob_minter_id = read_state(|s| s.bob_minter_id);
 
     let res_gov: Result<(Result<(), String>,), (i32, String)> =
-        ic_cdk::api::call::call(bob_minter_id, "submit_burned_cycles", (cycles,))
-            .await
-            .map_err(|(code, msg)| (code as i32, msg));
-    match res_gov {
-        Ok((res,)) => Ok(res?),
+        ic_cdk::api::call::call_with_payment128(
+            bob_minter_id,
+            "submit_burned_cycles",
+            (cycles as u64,),
+            cycles,
+        )
+        .await
+        .map_err(|(code, msg)| (code as i32, msg));
+    let burned_cycles = cycles.saturating_sub(ic_cdk::api::call::msg_cycles_refunded128());
+    let result = match res_gov {
+        Ok((res,)) => res,
         Err((code, msg)) => Err(format!(
             "Error while calling minter canister ({}): {:?}",
             code, msg
         )),
+    };
+    (burned_cycles, result)
+}
+
+/// Hash of a challenge solution, the miner is part of the preimage so that
+/// solutions cannot be replayed by other miners.
+pub fn challenge_hash(challenge: &[u8; 32], miner: &Principal, nonce: u64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(challenge);
+    hasher.update(miner.as_slice());
+    hasher.update(nonce.to_le_bytes());
+    hasher.finalize().into()
+}
+
+pub fn leading_zero_bits(hash: &[u8; 32]) -> u64 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros() as u64;
+        if *byte != 0 {
+            break;
+        }
     }
+    bits
 }
 
-thread_local! {
-    static __STATE: RefCell<Option<State>> = RefCell::default();
+/// Starts searching a solution to the current challenge unless a search is
+/// already running.
+pub fn start_challenge_search() {
+    if !__SEARCHING.with(|s| s.replace(true)) {
+        ic_cdk_timers::set_timer(Duration::ZERO, search_challenge);
+    }
 }
 
-#[derive(Clone, CandidType)]
-pub struct State {
-    pub bob_minter_id: Principal,
-    pub owner: Principal,
-    pub solved_challenges: u64,
-    pub hashes_computed: u128,
-    pub max_cycles_per_round: u128,
-    pub last_cycles_burned: u128,
+/// Tries nonces for the current challenge until the instruction budget of
+/// the message is spent, then continues in a new message.
+fn search_challenge() {
+    let now = ic_cdk::api::time();
+    let (challenge, reserve_cycles) = read_state(|s| (s.active_challenge(now), s.reserve_cycles));
+    let challenge = match challenge {
+        Some(challenge) if ic_cdk::api::canister_balance128() > reserve_cycles => challenge,
+        _ => {
+            __SEARCHING.with(|s| s.set(false));
+            return;
+        }
+    };
+    // The previous submission failed, retry it instead of searching again.
+    if let Some(nonce) = challenge.solution {
+        submit_challenge_solution(challenge.value, nonce);
+        return;
+    }
+
+    let miner = ic_cdk::id();
+    let mut nonce = challenge.next_nonce;
+    let mut hashes_computed: u128 = 0;
+    let solution = loop {
+        let hash = challenge_hash(&challenge.value, &miner, nonce);
+        hashes_computed += 1;
+        nonce = nonce.wrapping_add(1);
+        if leading_zero_bits(&hash) >= challenge.difficulty {
+            break Some(nonce.wrapping_sub(1));
+        }
+        if ic_cdk::api::instruction_counter() > SEARCH_INSTRUCTION_LIMIT {
+            break None;
+        }
+    };
+
+    mutate_state(|s| {
+        s.hashes_computed += hashes_computed;
+        if let Some(current) = s.challenge.as_mut().filter(|c| c.value == challenge.value) {
+            current.next_nonce = nonce;
+            current.solution = solution;
+        }
+    });
+
+    match solution {
+        Some(nonce) => submit_challenge_solution(challenge.value, nonce),
+        None => {
+            ic_cdk_timers::set_timer(Duration::ZERO, search_challenge);
+        }
+    }
 }
 
-impl State {
-    pub fn from_init(owner: Principal) -> Self {
-        let bob_minter_id = Principal::from_text("6lnhz-oaaaa-aaaas-aabkq-cai").unwrap();
-        Self {
-            bob_minter_id,
-            solved_challenges: 0,
-            hashes_computed: 0,
-            owner,
-            max_cycles_per_round: DEFAULT_BURNED_CYCLES_PER_ROUND,
-            last_cycles_burned: 0,
+/// Submits the solution, the challenge is kept until the minter accepts it
+/// so that a failed submission is retried on the next broadcast.
+fn submit_challenge_solution(challenge: [u8; 32], nonce: u64) {
+    ic_cdk::spawn(async move {
+        if submit_solution(challenge, nonce).await.is_ok() {
+            mutate_state(|s| {
+                if s.challenge.as_ref().is_some_and(|c| c.value == challenge) {
+                    s.challenge = None;
+                }
+            });
         }
+        __SEARCHING.with(|s| s.set(false));
+    });
+}
+
+async fn submit_solution(challenge: [u8; 32], nonce: u64) -> Result<(), String> {
+    let bob_minter_id = read_state(|s| s.bob_minter_id);
+
+    let res: Result<(Result<(), String>,), _> =
+        ic_cdk::call(bob_minter_id, "submit_solution", (challenge, nonce)).await;
+    match res {
+        Ok((res,)) => res,
+        Err((code, msg)) => Err(format!(
+            "Error while calling minter canister ({:?}): {:?}",
+            code, msg
+        )),
     }
 }
 
+thread_local! {
+    static __STATE: RefCell<Option<State>> = RefCell::default();
+    static __SEARCHING: Cell<bool> = Cell::default();
+}
+
 pub fn mutate_state<F, R>(f: F) -> R
 where
     F: FnOnce(&mut State) -> R,
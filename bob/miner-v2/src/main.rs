@@ -1,70 +1,123 @@
-use bob_miner_v2::{mutate_state, process_logic, read_state, replace_state, State};
-use candid::{CandidType, Deserialize, Principal};
-use ic_cdk::{init, query, update};
+use bob_miner_v2::memory::{get_rounds, has_memory_manager_layout, load_state, save_state};
+use bob_miner_v2::state::StateExt;
+use bob_miner_v2::{
+    mutate_state, process_logic, read_state, replace_state, start_challenge_search, MinerSettings,
+    OperatorPermission, Round, State, StatsV2,
+};
+use candid::Principal;
+use ic_cdk::{init, post_upgrade, pre_upgrade, query, update};
+use ic_cdk_timers::TimerId;
+use std::cell::Cell;
 use std::time::Duration;
 
 fn main() {}
 
 #[init]
 fn init(owner: Principal) {
+    replace_state(State::from_init(owner));
+
     setup_timer();
+}
 
-    replace_state(State::from_init(owner));
+#[pre_upgrade]
+fn pre_upgrade() {
+    save_state(read_state(|s| s.clone()));
 }
 
-const ROUND_LENGTH_SECS: u64 = 240;
+#[post_upgrade]
+fn post_upgrade(owner: Principal) {
+    // Miners installed before the state was persisted have nothing to restore.
+    if ic_cdk::api::stable::stable_size() == 0 {
+        replace_state(State::from_init(owner));
+    } else if !has_memory_manager_layout() {
+        ic_cdk::trap("unexpected stable memory layout");
+    } else {
+        replace_state(load_state().unwrap_or_else(|| ic_cdk::trap("no miner state to restore")));
+    }
+
+    setup_timer();
+    if read_state(|s| s.active_challenge(ic_cdk::api::time()).is_some()) {
+        start_challenge_search();
+    }
+}
+
+thread_local! {
+    static TIMER_ID: Cell<Option<TimerId>> = Cell::default();
+}
 
 fn setup_timer() {
-    ic_cdk_timers::set_timer_interval(Duration::from_secs(ROUND_LENGTH_SECS), || {
-        ic_cdk::spawn(async {
-            let _ = process_logic().await;
-        })
-    });
+    if let Some(timer_id) = TIMER_ID.with(|t| t.take()) {
+        ic_cdk_timers::clear_timer(timer_id);
+    }
+
+    let round_length_secs = read_state(|s| s.round_length_secs);
+    let timer_id =
+        ic_cdk_timers::set_timer_interval(Duration::from_secs(round_length_secs), || {
+            ic_cdk::spawn(async {
+                let _ = process_logic().await;
+            })
+        });
+    TIMER_ID.with(|t| t.set(Some(timer_id)));
 }
 
 #[update]
-fn push_challenge(_challenge: [u8; 32], _difficulty: u64) {
+fn push_challenge(challenge: [u8; 32], difficulty: u64) {
     let bob_minter_id = read_state(|s| s.bob_minter_id);
     assert_eq!(ic_cdk::caller(), bob_minter_id);
+    mutate_state(|s| s.challenge_received(challenge, difficulty, ic_cdk::api::time()));
+    start_challenge_search();
 }
 
-#[derive(CandidType, Deserialize)]
-struct MinerSettings {
-    max_cycles_per_round: Option<u128>,
-    new_owner: Option<Principal>,
+#[update]
+fn update_miner_settings(settings: MinerSettings) {
+    if let Err(e) = read_state(|s| s.check_settings_caller(ic_cdk::caller(), &settings)) {
+        ic_cdk::trap(&e);
+    }
+    let round_length_secs = read_state(|s| s.round_length_secs);
+    if let Err(e) = mutate_state(|s| s.apply_settings(settings)) {
+        ic_cdk::trap(&e);
+    }
+    if read_state(|s| s.round_length_secs) != round_length_secs {
+        setup_timer();
+    }
 }
 
+/// Grants the operator the given permissions, replacing the previous ones.
+/// Passing no permissions revokes the operator.
 #[update]
-fn update_miner_settings(settings: MinerSettings) {
+fn set_operator(operator: Principal, permissions: Vec<OperatorPermission>) {
     if ic_cdk::caller() != read_state(|s| s.owner) {
         ic_cdk::trap("caller not owner");
     }
-    mutate_state(|s| {
-        if let Some(hash_limit_per_round) = settings.max_cycles_per_round {
-            s.max_cycles_per_round = hash_limit_per_round;
-        }
-
-        if let Some(new_owner) = settings.new_owner {
-            s.owner = new_owner;
-        }
-    })
+    mutate_state(|s| s.set_operator(operator, permissions));
+}
+
+#[query]
+fn get_operator_permissions(operator: Principal) -> Vec<OperatorPermission> {
+    read_state(|s| s.operator_permissions(operator))
 }
 
-#[derive(CandidType)]
-struct StatsV2 {
-    cycle_balance: u64,
-    cycles_burned_per_round: u128,
-    round_length_secs: u64,
-    last_round_cyles_burned: u128,
+#[update]
+fn notify_block_won(rewards: u64) {
+    let bob_minter_id = read_state(|s| s.bob_minter_id);
+    assert_eq!(ic_cdk::caller(), bob_minter_id);
+    mutate_state(|s| s.record_block_won(rewards));
+}
+
+#[query]
+fn get_round_history(offset: u64, length: u64) -> Vec<Round> {
+    const MAX_LENGTH: u64 = 100;
+    get_rounds(offset, length.min(MAX_LENGTH))
 }
 
 #[query]
 fn get_statistics_v2() -> StatsV2 {
     read_state(|s| StatsV2 {
         cycle_balance: ic_cdk::api::canister_balance(),
-        cycles_burned_per_round: s.max_cycles_per_round,
-        round_length_secs: ROUND_LENGTH_SECS,
+        cycles_burned_per_round: s.cycles_per_round(),
+        round_length_secs: s.round_length_secs,
         last_round_cyles_burned: s.last_cycles_burned,
+        runway_days: s.runway_days(ic_cdk::api::canister_balance128()),
     })
 }
 
@@ -0,0 +1,104 @@
+use crate::{Round, State, MAX_ROUND_HISTORY};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager as MM, VirtualMemory};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl as DefMem, StableBTreeMap, StableCell, Storable};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+#[derive(Default, Clone)]
+struct Cbor<T>(pub T)
+where
+    T: serde::Serialize + serde::de::DeserializeOwned;
+
+impl<T> Storable for Cbor<T>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut buf = vec![];
+        ciborium::ser::into_writer(&self.0, &mut buf).unwrap();
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(ciborium::de::from_reader(bytes.as_ref()).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// NOTE: ensure that all memory ids are unique and
+// do not change across upgrades!
+const STATE_ID: MemoryId = MemoryId::new(0);
+const ROUND_HISTORY_ID: MemoryId = MemoryId::new(1);
+
+// Magic bytes the memory manager writes at the start of the stable memory.
+const MEMORY_MANAGER_MAGIC: &[u8; 3] = b"MGR";
+
+type VM = VirtualMemory<DefMem>;
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MM<DefMem>> = RefCell::new(
+        MM::init(DefMem::default())
+    );
+
+    static STATE: RefCell<StableCell<Cbor<Option<State>>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableCell::init(mm.borrow().get(STATE_ID), Cbor(None))
+            .expect("failed to initialize the state"))
+        });
+
+    static ROUND_HISTORY: RefCell<StableBTreeMap<u64, Cbor<Round>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableBTreeMap::init(mm.borrow().get(ROUND_HISTORY_ID)))
+        });
+}
+
+/// Returns true if the stable memory is laid out by the memory manager. The
+/// memory manager silently reinitializes any other content, so this must be
+/// checked before touching the stable structures.
+pub fn has_memory_manager_layout() -> bool {
+    let mut magic = [0; 3];
+    ic_cdk::api::stable::stable_read(0, &mut magic);
+    &magic == MEMORY_MANAGER_MAGIC
+}
+
+pub fn save_state(state: State) {
+    STATE
+        .with(|s| s.borrow_mut().set(Cbor(Some(state))))
+        .expect("failed to save the state");
+}
+
+pub fn load_state() -> Option<State> {
+    STATE.with(|s| s.borrow().get().0.clone())
+}
+
+/// Appends the round to the history, dropping the oldest rounds beyond
+/// `MAX_ROUND_HISTORY`.
+pub fn push_round(round: Round) {
+    ROUND_HISTORY.with(|h| {
+        let mut history = h.borrow_mut();
+        let index = history.last_key_value().map(|(k, _)| k + 1).unwrap_or(0);
+        history.insert(index, Cbor(round));
+        while history.len() > MAX_ROUND_HISTORY as u64 {
+            match history.first_key_value() {
+                Some((oldest, _)) => history.remove(&oldest),
+                None => break,
+            };
+        }
+    });
+}
+
+/// Returns up to `length` rounds, most recent first, skipping the `offset`
+/// most recent ones.
+pub fn get_rounds(offset: u64, length: u64) -> Vec<Round> {
+    ROUND_HISTORY.with(|h| {
+        h.borrow()
+            .iter()
+            .rev()
+            .skip(offset as usize)
+            .take(length as usize)
+            .map(|(_, round)| round.0)
+            .collect()
+    })
+}
@@ -0,0 +1,336 @@
+use crate::{
+    ActiveHours, Challenge, MinerSettings, OperatorPermission, State, DEFAULT_RESERVE_CYCLES,
+    DEFAULT_ROUND_LENGTH_SECS,
+};
+use candid::Principal;
+use std::collections::BTreeMap;
+
+const DEFAULT_BURNED_CYCLES_PER_ROUND: u128 = 10_000_000_001;
+
+// Rounds stay above the delay the minter enforces between two submissions.
+pub const MIN_ROUND_LENGTH_SECS: u64 = 120;
+
+pub const MAX_ROUND_LENGTH_SECS: u64 = 24 * 60 * 60;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+// The minter broadcasts open challenges every minute, a challenge that was
+// not broadcast for this long is considered over.
+const CHALLENGE_TTL_NANOS: u64 = 10 * 60 * 1_000_000_000;
+
+pub const ALL_OPERATOR_PERMISSIONS: [OperatorPermission; 3] = [
+    OperatorPermission::AdjustBurnRate,
+    OperatorPermission::Upgrade,
+    OperatorPermission::TopUp,
+];
+
+/// Whether the hour of the day (UTC) falls in the active hours.
+fn is_active_hour(active_hours: &ActiveHours, hour: u8) -> bool {
+    if active_hours.start_hour <= active_hours.end_hour {
+        active_hours.start_hour <= hour && hour < active_hours.end_hour
+    } else {
+        hour >= active_hours.start_hour || hour < active_hours.end_hour
+    }
+}
+
+fn active_secs_per_day(active_hours: &ActiveHours) -> u64 {
+    let hours = (active_hours.end_hour as u64 + 24 - active_hours.start_hour as u64) % 24;
+    hours * 60 * 60
+}
+
+/// The behaviour of the miner state, whose type is shared with the clients
+/// of the miner through bob-types.
+pub trait StateExt {
+    fn from_init(owner: Principal) -> Self;
+
+    /// Replaces the permissions of the operator, no permissions removes it.
+    fn set_operator(&mut self, operator: Principal, permissions: Vec<OperatorPermission>);
+
+    /// The owner holds every permission.
+    fn operator_permissions(&self, principal: Principal) -> Vec<OperatorPermission>;
+
+    fn has_permission(&self, principal: Principal, permission: OperatorPermission) -> bool;
+
+    /// Operators can adjust the burn rate but only the owner can hand over
+    /// the miner.
+    fn check_settings_caller(
+        &self,
+        caller: Principal,
+        settings: &MinerSettings,
+    ) -> Result<(), String>;
+
+    /// Records a challenge broadcast by the minter, the search of a challenge
+    /// broadcast again resumes where it stopped.
+    fn challenge_received(&mut self, value: [u8; 32], difficulty: u64, now: u64);
+
+    fn active_challenge(&self, now: u64) -> Option<Challenge>;
+
+    fn record_block_won(&mut self, rewards: u64);
+
+    fn apply_settings(&mut self, settings: MinerSettings) -> Result<(), String>;
+
+    fn is_active_at(&self, now_secs: u64) -> bool;
+
+    fn daily_burn(&self) -> u128;
+
+    fn runway_days(&self, cycle_balance: u128) -> Option<u64>;
+
+    /// Cycles to burn each round, spreading the target daily burn over the
+    /// active rounds of the day without exceeding `max_cycles_per_round`.
+    fn cycles_per_round(&self) -> u128;
+}
+
+impl StateExt for State {
+    fn from_init(owner: Principal) -> Self {
+        let bob_minter_id = Principal::from_text("6lnhz-oaaaa-aaaas-aabkq-cai").unwrap();
+        Self {
+            bob_minter_id,
+            solved_challenges: 0,
+            hashes_computed: 0,
+            owner,
+            max_cycles_per_round: DEFAULT_BURNED_CYCLES_PER_ROUND,
+            last_cycles_burned: 0,
+            round_length_secs: DEFAULT_ROUND_LENGTH_SECS,
+            target_daily_burn: None,
+            reserve_cycles: DEFAULT_RESERVE_CYCLES,
+            active_hours: None,
+            rewards_won: 0,
+            challenge: None,
+            operators: BTreeMap::new(),
+        }
+    }
+
+    fn set_operator(&mut self, operator: Principal, permissions: Vec<OperatorPermission>) {
+        if permissions.is_empty() {
+            self.operators.remove(&operator);
+        } else {
+            self.operators
+                .insert(operator, permissions.into_iter().collect());
+        }
+    }
+
+    fn operator_permissions(&self, principal: Principal) -> Vec<OperatorPermission> {
+        if principal == self.owner {
+            return ALL_OPERATOR_PERMISSIONS.to_vec();
+        }
+        self.operators
+            .get(&principal)
+            .map(|permissions| permissions.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn has_permission(&self, principal: Principal, permission: OperatorPermission) -> bool {
+        principal == self.owner
+            || self
+                .operators
+                .get(&principal)
+                .is_some_and(|permissions| permissions.contains(&permission))
+    }
+
+    fn check_settings_caller(
+        &self,
+        caller: Principal,
+        settings: &MinerSettings,
+    ) -> Result<(), String> {
+        if caller == self.owner {
+            return Ok(());
+        }
+        if !self.has_permission(caller, OperatorPermission::AdjustBurnRate) {
+            return Err("caller not owner".to_string());
+        }
+        if settings.new_owner.is_some() {
+            return Err("only the owner can transfer the miner".to_string());
+        }
+        Ok(())
+    }
+
+    fn challenge_received(&mut self, value: [u8; 32], difficulty: u64, now: u64) {
+        match self.challenge.as_mut() {
+            Some(challenge) if challenge.value == value => challenge.received_at = now,
+            _ => {
+                self.challenge = Some(Challenge {
+                    value,
+                    difficulty,
+                    received_at: now,
+                    next_nonce: 0,
+                    solution: None,
+                })
+            }
+        }
+    }
+
+    fn active_challenge(&self, now: u64) -> Option<Challenge> {
+        self.challenge
+            .clone()
+            .filter(|c| now.saturating_sub(c.received_at) < CHALLENGE_TTL_NANOS)
+    }
+
+    fn record_block_won(&mut self, rewards: u64) {
+        self.solved_challenges += 1;
+        self.rewards_won += rewards;
+    }
+
+    fn apply_settings(&mut self, settings: MinerSettings) -> Result<(), String> {
+        if let Some(round_length_secs) = settings.round_length_secs {
+            if !(MIN_ROUND_LENGTH_SECS..=MAX_ROUND_LENGTH_SECS).contains(&round_length_secs) {
+                return Err(format!(
+                    "round length must be between {MIN_ROUND_LENGTH_SECS} and {MAX_ROUND_LENGTH_SECS} seconds"
+                ));
+            }
+        }
+        if let Some(active_hours) = settings.active_hours {
+            if active_hours.start_hour >= 24 || active_hours.end_hour >= 24 {
+                return Err("active hours must be between 0 and 23".to_string());
+            }
+        }
+
+        if let Some(hash_limit_per_round) = settings.max_cycles_per_round {
+            self.max_cycles_per_round = hash_limit_per_round;
+        }
+        if let Some(new_owner) = settings.new_owner {
+            if new_owner != self.owner {
+                self.operators.clear();
+            }
+            self.owner = new_owner;
+        }
+        if let Some(round_length_secs) = settings.round_length_secs {
+            self.round_length_secs = round_length_secs;
+        }
+        if let Some(target_daily_burn) = settings.target_daily_burn {
+            self.target_daily_burn = Some(target_daily_burn).filter(|target| *target > 0);
+        }
+        if let Some(reserve_cycles) = settings.reserve_cycles {
+            self.reserve_cycles = reserve_cycles;
+        }
+        if let Some(active_hours) = settings.active_hours {
+            self.active_hours =
+                Some(active_hours).filter(|hours| hours.start_hour != hours.end_hour);
+        }
+        Ok(())
+    }
+
+    fn is_active_at(&self, now_secs: u64) -> bool {
+        let hour = ((now_secs % SECS_PER_DAY) / (60 * 60)) as u8;
+        match self.active_hours {
+            Some(active_hours) => is_active_hour(&active_hours, hour),
+            None => true,
+        }
+    }
+
+    fn daily_burn(&self) -> u128 {
+        let active_secs = self.active_hours.map_or(SECS_PER_DAY, |active_hours| {
+            active_secs_per_day(&active_hours)
+        });
+        self.cycles_per_round() * (active_secs / self.round_length_secs) as u128
+    }
+
+    fn runway_days(&self, cycle_balance: u128) -> Option<u64> {
+        let daily_burn = self.daily_burn();
+        if daily_burn == 0 {
+            return None;
+        }
+        let days = cycle_balance.saturating_sub(self.reserve_cycles) / daily_burn;
+        Some(days.min(u64::MAX as u128) as u64)
+    }
+
+    fn cycles_per_round(&self) -> u128 {
+        match self.target_daily_burn {
+            Some(target_daily_burn) => {
+                let active_secs = self.active_hours.map_or(SECS_PER_DAY, |active_hours| {
+                    active_secs_per_day(&active_hours)
+                });
+                let rounds_per_day = (active_secs / self.round_length_secs).max(1);
+                (target_daily_burn / rounds_per_day as u128).min(self.max_cycles_per_round)
+            }
+            None => self.max_cycles_per_round,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NIGHT_SHIFT: ActiveHours = ActiveHours {
+        start_hour: 22,
+        end_hour: 2,
+    };
+
+    fn state() -> State {
+        State::from_init(Principal::anonymous())
+    }
+
+    #[test]
+    fn should_burn_the_max_without_target() {
+        assert_eq!(state().cycles_per_round(), DEFAULT_BURNED_CYCLES_PER_ROUND);
+    }
+
+    #[test]
+    fn should_spread_the_target_over_the_rounds_of_the_day() {
+        let mut state = state();
+        // 360 rounds of 240 seconds per day
+        state.target_daily_burn = Some(360_000_000_000);
+        assert_eq!(state.cycles_per_round(), 1_000_000_000);
+
+        // 60 rounds during the 4 active hours
+        state.active_hours = Some(NIGHT_SHIFT);
+        assert_eq!(state.cycles_per_round(), 6_000_000_000);
+
+        state.target_daily_burn = Some(u128::MAX);
+        assert_eq!(state.cycles_per_round(), DEFAULT_BURNED_CYCLES_PER_ROUND);
+    }
+
+    #[test]
+    fn should_count_the_runway_above_the_reserve() {
+        let mut state = state();
+        state.max_cycles_per_round = 20_000_000_000;
+        state.round_length_secs = 300;
+        // 288 rounds per day
+        let daily_burn = 20_000_000_000 * 288;
+        assert_eq!(state.runway_days(DEFAULT_RESERVE_CYCLES), Some(0));
+        assert_eq!(
+            state.runway_days(DEFAULT_RESERVE_CYCLES + 3 * daily_burn + 1),
+            Some(3)
+        );
+        assert_eq!(state.runway_days(0), Some(0));
+
+        // 48 rounds during the 4 active hours
+        state.active_hours = Some(NIGHT_SHIFT);
+        assert_eq!(
+            state.runway_days(DEFAULT_RESERVE_CYCLES + 20_000_000_000 * 48 * 10),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn should_contain_hours_of_a_window_wrapping_past_midnight() {
+        for hour in [22, 23, 0, 1] {
+            assert!(is_active_hour(&NIGHT_SHIFT, hour), "{hour}");
+        }
+        for hour in [2, 12, 21] {
+            assert!(!is_active_hour(&NIGHT_SHIFT, hour), "{hour}");
+        }
+        assert_eq!(active_secs_per_day(&NIGHT_SHIFT), 4 * 60 * 60);
+
+        let office_hours = ActiveHours {
+            start_hour: 9,
+            end_hour: 17,
+        };
+        assert!(is_active_hour(&office_hours, 9));
+        assert!(is_active_hour(&office_hours, 16));
+        assert!(!is_active_hour(&office_hours, 17));
+        assert!(!is_active_hour(&office_hours, 8));
+    }
+
+    #[test]
+    fn should_be_active_at_hours_of_the_window_on_any_day() {
+        let mut state = state();
+        assert!(state.is_active_at(12 * 60 * 60));
+
+        state.active_hours = Some(NIGHT_SHIFT);
+        assert!(state.is_active_at(23 * 60 * 60));
+        assert!(state.is_active_at(5 * SECS_PER_DAY + 60 * 60 + 59 * 60));
+        assert!(!state.is_active_at(2 * 60 * 60));
+        assert!(!state.is_active_at(5 * SECS_PER_DAY + 12 * 60 * 60));
+    }
+}
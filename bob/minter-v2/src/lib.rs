@@ -1,47 +1,93 @@
 use crate::guard::TaskGuard;
 use crate::memory::{
-    get_block_to_mine, get_expire_map, get_miner_owner, insert_block_to_mine, push_block,
-    remove_block_to_mine, remove_expired_entries, should_mine, user_count,
+    get_auto_top_ups, get_block, get_block_to_mine, get_challenge, get_expire_map, get_miner_owner,
+    get_miner_rollout, get_miner_to_owner_and_index, get_miner_wasm, get_miner_wasm_version,
+    insert_block_to_mine, mined_block_count, mutate_miner_rollout, push_block, push_miner_refill,
+    remove_block_to_mine, remove_expired_entries, set_challenge, set_miner_wasm_version,
+    should_mine, user_count,
 };
+use crate::miner::{canister_cycles, start_canister, stop_canister, upgrade_code};
 use crate::tasks::{schedule_after, schedule_now, TaskType};
+use bob_miner_v2::{challenge_hash, leading_zero_bits};
 use candid::{CandidType, Decode, Encode, Nat, Principal};
 use cycles_minting_canister::NotifyError;
+use ic_base_types::PrincipalId;
 use ic_ledger_core::block::BlockType;
+use ic_management_canister_types::{CanisterSettingsArgs, CanisterSettingsArgsBuilder};
 use ic_types::Cycles;
 use icrc_ledger_client_cdk::{CdkRuntime, ICRC1Client};
 use icrc_ledger_types::icrc1::account::Account;
-use icrc_ledger_types::icrc1::transfer::{TransferArg, TransferError};
+use icrc_ledger_types::icrc1::transfer::{Memo, TransferArg, TransferError};
+use icrc_ledger_types::icrc2::transfer_from::TransferFromArgs;
 use rand::distributions::Standard;
 use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::{BTreeMap, BTreeSet};
 use std::time::Duration;
 
 // Initial reward per block of 600 BOB
-const COINBASE_REWARDS: u64 = 60_000_000_000;
+pub const COINBASE_REWARDS: u64 = 60_000_000_000;
 pub const BLOCK_HALVING: u64 = 17_500;
+// Blocks mined by the previous version of the minter.
+pub const HISTORICAL_BLOCKS: u64 = 1_441;
+// Number of recent blocks used to estimate the average block time.
+const BLOCK_TIME_SAMPLE: u64 = 100;
 
 pub const SEC_NANOS: u64 = 1_000_000_000;
 pub const DAY_NANOS: u64 = 24 * 60 * 60 * SEC_NANOS;
 
 const CYCLES_PER_USER_PER_ROUND: u64 = 15_000_000_000;
 
+// Default round length of the miners spawned by this canister.
+const MINER_ROUND_LENGTH_SECS: u64 = 240;
+
+// The cycles pool, registered as a miner burning on behalf of its users.
+pub const POOL_CANISTER_ID: Principal =
+    Principal::from_slice(&[0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x14, 0x74, 0x01, 0x01]);
+
 pub const MAINNET_LEDGER_CANISTER_ID: Principal =
     Principal::from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x01, 0x01]);
 
 pub const MAINNET_CYCLE_MINTER_CANISTER_ID: Principal =
     Principal::from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x01, 0x01]);
 
+// Memo of ICP transfers topping up this canister through the CMC.
+pub const TOP_UP_MEMO: u64 = 1347768404;
+// Account of the CMC topping up this canister.
+pub const TOP_UP_ACCOUNT: &str = "e7b583c3e3e2837c987831a97a6b980cbb0be89819e85915beb3c02006923fce";
+
+pub const ICP_TRANSFER_FEE: u64 = 10_000;
+// Price of a miner, or of one day in the pool, in ICP e8s.
+pub const ICP_PER_MINER: u64 = 100_000_000;
+
+pub const CYCLES_FOR_CREATION: u64 = 2_500_000_000_000;
+// Price of one day in the pool when paying with cycles.
+pub const CYCLES_PER_POOL_DAY: u128 = 5_000_000_000_000;
+// Block index recorded for miners paid with cycles.
+pub const CYCLES_PAYMENT_BLOCK_INDEX: u64 = u64::MAX;
+
+// Delay between two broadcasts of the open challenge to the miners.
+const CHALLENGE_BROADCAST_INTERVAL: Duration = Duration::from_secs(60);
+
+// Delay between two checks of the miners with an auto top-up.
+const MINER_TOP_UP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// Smallest amount of ICP an auto top-up can pull at once.
+pub const MIN_AUTO_TOP_UP_E8S: u64 = 10_000_000;
+
 pub mod guard;
 pub mod memory;
 pub mod miner;
 pub mod tasks;
 
+pub use bob_types::miner::OperatorPermission;
+pub use bob_types::minter::*;
+
 #[derive(Debug, Clone)]
 pub struct MinerWasm;
 
@@ -49,6 +95,27 @@ pub fn miner_wasm() -> Cow<'static, [u8]> {
     Cow::Borrowed(include_bytes!(env!("MINER_WASM_PATH")))
 }
 
+// Version of the miner wasm embedded in this canister.
+pub const EMBEDDED_MINER_WASM_VERSION: u64 = 0;
+// Delay between two waves of a miner rollout.
+const MINER_ROLLOUT_WAVE_DELAY: Duration = Duration::from_secs(60);
+
+/// Returns the miner wasm of the given version, either embedded or uploaded
+/// by the controllers.
+pub fn load_miner_wasm(version: u64) -> Option<Cow<'static, [u8]>> {
+    if version == EMBEDDED_MINER_WASM_VERSION {
+        return Some(miner_wasm());
+    }
+    get_miner_wasm(version).map(Cow::Owned)
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
 pub fn next_block_time(seed: [u8; 32]) -> u64 {
     let mut rng = StdRng::from_seed(seed);
 
@@ -80,6 +147,29 @@ pub fn timer() {
                     let _ = mine_block().await;
                 });
             }
+            TaskType::UpgradeMiners => {
+                ic_cdk::spawn(async move {
+                    let _guard = match TaskGuard::new(task_type) {
+                        Ok(guard) => guard,
+                        Err(_) => return,
+                    };
+
+                    if let Ok(true) = upgrade_miners_wave().await {
+                        schedule_after(MINER_ROLLOUT_WAVE_DELAY, TaskType::UpgradeMiners);
+                    }
+                });
+            }
+            TaskType::TopUpMiners => {
+                ic_cdk::spawn(async move {
+                    let _guard = match TaskGuard::new(task_type) {
+                        Ok(guard) => guard,
+                        Err(_) => return,
+                    };
+
+                    top_up_miners().await;
+                    schedule_after(MINER_TOP_UP_INTERVAL, TaskType::TopUpMiners);
+                });
+            }
             TaskType::ProcessLogic => {
                 ic_cdk::spawn(async move {
                     let _guard = match TaskGuard::new(task_type) {
@@ -114,7 +204,7 @@ fn burn_from_pool() {
 
     let burned_cycles = ic_cdk::api::cycles_burn(cycles_per_round as u128) as u64;
 
-    let pool_id = Principal::from_text("zje3u-qaaaa-aaaai-acr2a-cai").unwrap();
+    let pool_id = POOL_CANISTER_ID;
 
     mutate_state(|s| {
         s.miner_to_burned_cycles
@@ -127,6 +217,12 @@ fn burn_from_pool() {
 pub async fn process_logic() -> Result<(), String> {
     use ic_cdk::api::management_canister::main::raw_rand;
 
+    let config = crate::memory::get_config();
+    if config.block_selection == BlockSelection::HashChallenge {
+        return broadcast_challenge(config.challenge_difficulty).await;
+    }
+    set_challenge(None);
+
     if let Ok((random_array,)) = raw_rand().await {
         burn_from_pool();
         let total_cycles: u64 = read_state(|s| s.miner_to_burned_cycles.values().sum());
@@ -173,6 +269,38 @@ pub async fn process_logic() -> Result<(), String> {
     Ok(())
 }
 
+/// Opens a new challenge if none is open and sends the open challenge to
+/// all the miners. The challenge stays open until a miner solves it.
+async fn broadcast_challenge(difficulty: u64) -> Result<(), String> {
+    use ic_cdk::api::management_canister::main::raw_rand;
+
+    if get_challenge().is_none() {
+        let (random_array,) = raw_rand()
+            .await
+            .map_err(|_| "Failed to generate random value".to_string())?;
+        let challenge = Challenge {
+            value: random_array.try_into().unwrap(),
+            difficulty,
+            issued_at: ic_cdk::api::time(),
+        };
+        set_challenge(Some(challenge));
+    }
+
+    let challenge = get_challenge().ok_or("no open challenge")?;
+    let pool_id = POOL_CANISTER_ID;
+    for (miner, _) in get_miner_to_owner_and_index() {
+        if miner != pool_id {
+            let _ = ic_cdk::api::call::notify(
+                miner,
+                "push_challenge",
+                (challenge.value, challenge.difficulty),
+            );
+        }
+    }
+    schedule_after(CHALLENGE_BROADCAST_INTERVAL, TaskType::ProcessLogic);
+    Ok(())
+}
+
 pub async fn transfer(
     to: impl Into<Account>,
     amount: Nat,
@@ -208,7 +336,7 @@ pub async fn mine_block() -> Result<(), String> {
     let blocks = get_block_to_mine();
     let ledger_canister_id = read_state(|s| s.bob_ledger_id);
     for block in blocks {
-        let pool_id = Principal::from_text("zje3u-qaaaa-aaaai-acr2a-cai").unwrap();
+        let pool_id = POOL_CANISTER_ID;
         if block.to == pool_id {
             let now = ic_cdk::api::time();
             remove_expired_entries(now);
@@ -237,6 +365,9 @@ pub async fn mine_block() -> Result<(), String> {
             .await
             {
                 Ok(_) => {
+                    if let Some(miner) = block.miner {
+                        notify_block_won(miner, block.rewards);
+                    }
                     remove_block_to_mine(block.clone());
                     push_block(block);
                 }
@@ -249,6 +380,184 @@ pub async fn mine_block() -> Result<(), String> {
     Ok(())
 }
 
+/// Lets the miner keep track of the blocks it won. Miners spawned before
+/// `notify_block_won` existed reject the call, which is ignored.
+fn notify_block_won(miner: Principal, rewards: u64) {
+    let _ = ic_cdk::api::call::notify(miner, "notify_block_won", (rewards,));
+}
+
+/// Checks that the caller owns the miner or was granted the permission by its
+/// owner. Operators are stored by the miner, so the minter asks the miner.
+pub async fn check_miner_permission(
+    miner: Principal,
+    caller: Principal,
+    permission: OperatorPermission,
+) -> Result<(), String> {
+    let owner = get_miner_owner(miner).ok_or("unknown miner")?;
+    if caller == owner {
+        return Ok(());
+    }
+    let result: Result<(Vec<OperatorPermission>,), _> =
+        ic_cdk::call(miner, "get_operator_permissions", (caller,)).await;
+    match result {
+        Ok((permissions,)) if permissions.contains(&permission) => Ok(()),
+        Ok(_) => Err(format!(
+            "caller is neither the owner nor an operator allowed to {permission:?} the miner"
+        )),
+        Err((code, msg)) => Err(format!(
+            "failed to fetch the operator permissions ({}): {msg}",
+            code as i32
+        )),
+    }
+}
+
+/// Account of the CMC topping up `canister_id` with the ICP it receives.
+pub fn cmc_top_up_account(canister_id: Principal) -> Account {
+    let subaccount = icp_ledger::Subaccount::from(&PrincipalId::from(canister_id));
+    Account {
+        owner: MAINNET_CYCLE_MINTER_CANISTER_ID,
+        subaccount: Some(subaccount.0),
+    }
+}
+
+/// Pulls `amount` ICP from `from` through an ICRC-2 approval straight into
+/// the CMC top-up account of `canister_id`, so that no ICP is left on this
+/// canister if the top-up fails. The CMC reads the memo of ICRC transfers as
+/// little-endian bytes. Returns the index of the transfer to pass to
+/// `notify_top_up`.
+pub async fn top_up_from(
+    from: Principal,
+    amount: u64,
+    canister_id: Principal,
+) -> Result<u64, String> {
+    let client = ICRC1Client {
+        runtime: CdkRuntime,
+        ledger_canister_id: MAINNET_LEDGER_CANISTER_ID,
+    };
+    let block_index = client
+        .transfer_from(TransferFromArgs {
+            spender_subaccount: None,
+            from: from.into(),
+            to: cmc_top_up_account(canister_id),
+            amount: Nat::from(amount),
+            fee: None,
+            memo: Some(Memo::from(TOP_UP_MEMO.to_le_bytes().to_vec())),
+            created_at_time: None,
+        })
+        .await
+        .map_err(|(code, msg)| {
+            format!("Error while calling ledger canister ({}): {:?}", code, msg)
+        })?
+        .map_err(|e| format!("transfer_from failed: {e:?}"))?;
+    Ok(block_index.0.try_into().unwrap())
+}
+
+/// Tops up the miners whose cycle balance dropped below their auto top-up
+/// threshold with ICP pulled from the allowance of their owner.
+pub async fn top_up_miners() {
+    for (miner, auto_top_up) in get_auto_top_ups() {
+        let owner = match get_miner_owner(miner) {
+            Some(owner) => owner,
+            None => continue,
+        };
+        let cycle_balance = match canister_cycles(miner).await {
+            Ok(cycle_balance) => cycle_balance,
+            Err(_) => continue,
+        };
+        if cycle_balance >= auto_top_up.threshold_cycles {
+            continue;
+        }
+
+        let result = match top_up_from(owner, auto_top_up.amount_e8s, miner).await {
+            Ok(block_index) => notify_top_up(block_index, miner)
+                .await
+                .map(|cycles| cycles.get()),
+            Err(e) => Err(e),
+        };
+        push_miner_refill(
+            miner,
+            MinerRefill {
+                timestamp: ic_cdk::api::time(),
+                amount_e8s: auto_top_up.amount_e8s,
+                cycle_balance,
+                result,
+            },
+        );
+    }
+}
+
+/// Upgrades the miner to the given wasm version, keeping its state. The miner is
+/// restarted even if the upgrade fails.
+pub async fn upgrade_miner(miner: Principal, owner: Principal, version: u64) -> Result<(), String> {
+    let wasm =
+        load_miner_wasm(version).ok_or_else(|| format!("unknown miner wasm version {version}"))?;
+    stop_canister(miner).await.map_err(|e| format!("{e:?}"))?;
+    let result = upgrade_code(miner, wasm.to_vec(), Encode!(&owner).unwrap())
+        .await
+        .map_err(|e| format!("{e:?}"));
+    start_canister(miner).await.map_err(|e| format!("{e:?}"))?;
+    result?;
+    set_miner_wasm_version(miner, version);
+    Ok(())
+}
+
+pub fn new_miner_rollout(args: MinerRolloutArgs, now: u64) -> MinerRollout {
+    MinerRollout {
+        version: args.version,
+        wave_size: args.wave_size,
+        max_failures: args.max_failures,
+        started_at: now,
+        completed_at: None,
+        upgraded: 0,
+        failed: BTreeMap::default(),
+    }
+}
+
+/// A rollout is halted once more miners than allowed failed to upgrade.
+pub fn is_rollout_active(rollout: &MinerRollout) -> bool {
+    rollout.completed_at.is_none() && rollout.failed.len() as u64 <= rollout.max_failures
+}
+
+/// Upgrades the next wave of miners of the ongoing rollout. Returns true if
+/// the rollout should continue with another wave.
+pub async fn upgrade_miners_wave() -> Result<bool, String> {
+    let rollout = match get_miner_rollout() {
+        Some(rollout) if is_rollout_active(&rollout) => rollout,
+        _ => return Ok(false),
+    };
+
+    let pool_id = POOL_CANISTER_ID;
+    let wave: Vec<(Principal, Principal)> = get_miner_to_owner_and_index()
+        .into_iter()
+        .filter(|(miner, _)| {
+            *miner != pool_id
+                && get_miner_wasm_version(*miner) != rollout.version
+                && !rollout.failed.contains_key(miner)
+        })
+        .take(rollout.wave_size as usize)
+        .map(|(miner, (owner, _))| (miner, owner))
+        .collect();
+
+    if wave.is_empty() {
+        mutate_miner_rollout(|rollout| rollout.completed_at = Some(ic_cdk::api::time()));
+        return Ok(false);
+    }
+
+    for (miner, owner) in wave {
+        let result = upgrade_miner(miner, owner, rollout.version).await;
+        mutate_miner_rollout(|rollout| match result {
+            Ok(()) => rollout.upgraded += 1,
+            Err(e) => {
+                rollout.failed.insert(miner, e);
+            }
+        });
+    }
+
+    Ok(get_miner_rollout()
+        .map(|rollout| is_rollout_active(&rollout))
+        .unwrap_or(false))
+}
+
 #[derive(CandidType)]
 struct NotifyTopUp {
     block_index: u64,
@@ -282,8 +591,7 @@ pub async fn fetch_block(block_height: u64) -> Result<icp_ledger::Block, String>
     }
 }
 
-pub async fn notify_top_up(block_height: u64) -> Result<Cycles, String> {
-    let canister_id = ic_cdk::id();
+pub async fn notify_top_up(block_height: u64, canister_id: Principal) -> Result<Cycles, String> {
     let args = Encode!(&NotifyTopUp {
         block_index: block_height,
         canister_id,
@@ -313,26 +621,69 @@ thread_local! {
     static __STATE: RefCell<Option<State>> = RefCell::default();
 }
 
-#[derive(Clone, CandidType, Ord, PartialOrd, Eq, PartialEq, Deserialize, Serialize, Debug)]
-pub struct Block {
-    pub to: Principal,
-    pub miner: Option<Principal>,
-    pub rewards: u64,
-    pub timestamp: u64,
-    pub total_cycles_burned: Option<u64>,
-    pub miner_cycles_burned: Option<u64>,
-    pub miner_count: Option<u64>,
+pub fn validate_config(config: &Config) -> Result<(), String> {
+    if config.miner_owner_is_controller && config.accept_unverified_burns {
+        return Err(
+            "unverified burns cannot be accepted from miners controlled by their owner".to_string(),
+        );
+    }
+    if config.max_submissions_per_round == 0 {
+        return Err("max submissions per round must be positive".to_string());
+    }
+    if config.challenge_difficulty > 256 {
+        return Err("challenge difficulty cannot exceed the 256 bits of a hash".to_string());
+    }
+    Ok(())
+}
+
+/// Canister settings of a miner spawned for `owner`.
+pub fn miner_canister_settings(config: &Config, owner: Principal) -> CanisterSettingsArgs {
+    let mut controllers = vec![PrincipalId::from(ic_cdk::id())];
+    if config.miner_owner_is_controller {
+        controllers.push(PrincipalId::from(owner));
+    }
+    CanisterSettingsArgsBuilder::new()
+        .with_controllers(controllers)
+        .with_freezing_threshold(config.miner_freezing_threshold_secs)
+        .with_reserved_cycles_limit(config.miner_reserved_cycles_limit)
+        .build()
+}
+
+/// Returns the reward of the block at the given height.
+pub fn block_rewards(height: u64) -> u64 {
+    let epoch = height / BLOCK_HALVING;
+    COINBASE_REWARDS.checked_shr(epoch as u32).unwrap_or(0)
+}
+
+/// Returns the amount of BOB emitted by the first `block_count` blocks.
+pub fn emitted_supply(block_count: u64) -> u64 {
+    let full_epochs = block_count / BLOCK_HALVING;
+    let mut emitted: u64 = (0..full_epochs.min(u64::BITS as u64))
+        .map(|epoch| BLOCK_HALVING * (COINBASE_REWARDS >> epoch))
+        .sum();
+    emitted += (block_count % BLOCK_HALVING) * block_rewards(block_count);
+    emitted
+}
+
+/// Returns the amount of BOB emitted once the block reward reaches zero.
+pub fn max_supply() -> u64 {
+    emitted_supply(u64::BITS as u64 * BLOCK_HALVING)
 }
 
-#[derive(CandidType, Deserialize, Debug)]
-pub struct Stats {
-    pub average_block_speed: u64,
-    pub block_count: u64,
-    pub miner_count: usize,
-    pub halving_count: u64,
-    pub cycle_balance: u64,
-    pub time_since_last_block: u64,
-    pub pending_blocks: Vec<Block>,
+/// Returns the average time between the most recent mined blocks.
+pub fn average_block_time_secs() -> Option<u64> {
+    let block_count = mined_block_count();
+    let start = block_count.saturating_sub(BLOCK_TIME_SAMPLE);
+    let timestamps: Vec<u64> = (start..block_count)
+        .filter_map(get_block)
+        .map(|block| block.timestamp)
+        .collect();
+    if timestamps.len() < 2 {
+        return None;
+    }
+    let first = timestamps.iter().min()?;
+    let last = timestamps.iter().max()?;
+    Some((last - first) / (timestamps.len() as u64 - 1) / SEC_NANOS)
 }
 
 #[derive(Clone, CandidType, Deserialize, Serialize, Debug)]
@@ -340,6 +691,9 @@ pub struct State {
     pub bob_ledger_id: Principal,
 
     pub miner_to_burned_cycles: BTreeMap<Principal, u64>,
+    /// Submissions of the current round, like the burned cycles they are
+    /// reset on upgrade, which starts a new round.
+    pub miner_submissions: BTreeMap<Principal, MinerSubmissions>,
 
     pub miner_to_mined_block: BTreeMap<Principal, u64>,
 
@@ -360,6 +714,7 @@ impl State {
             bob_ledger_id: Principal::from_text("7pail-xaaaa-aaaas-aabmq-cai").unwrap(),
 
             miner_to_burned_cycles: BTreeMap::default(),
+            miner_submissions: BTreeMap::default(),
 
             miner_to_mined_block: BTreeMap::default(),
 
@@ -380,7 +735,6 @@ impl State {
     }
 
     pub fn total_blocks_mined(&self) -> u64 {
-        const HISTORICAL_BLOCKS: u64 = 1_441;
         self.block_mined_count() + HISTORICAL_BLOCKS
     }
 
@@ -393,8 +747,63 @@ impl State {
             .push(miner);
     }
 
+    pub fn check_submission_rate(
+        &self,
+        miner: Principal,
+        now: u64,
+        config: &Config,
+    ) -> Result<(), String> {
+        if let Some(submissions) = self.miner_submissions.get(&miner) {
+            if submissions.count >= config.max_submissions_per_round {
+                return Err(format!(
+                    "Too many submissions this round, maximum is {}",
+                    config.max_submissions_per_round
+                ));
+            }
+            let elapsed_nanos = now.saturating_sub(submissions.last_submission_ts);
+            if elapsed_nanos
+                < config
+                    .min_submission_interval_secs
+                    .saturating_mul(SEC_NANOS)
+            {
+                return Err(format!(
+                    "Submitted too early, wait {} seconds between submissions",
+                    config.min_submission_interval_secs
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn record_submission(&mut self, miner: Principal, cycles: u64, now: u64) {
+        let submissions = self.miner_submissions.entry(miner).or_default();
+        submissions.count += 1;
+        submissions.last_submission_ts = now;
+        self.miner_to_burned_cycles
+            .entry(miner)
+            .and_modify(|e| *e += cycles)
+            .or_insert(cycles);
+    }
+
     pub fn current_rewards(&self) -> u64 {
-        COINBASE_REWARDS >> (self.total_blocks_mined() / BLOCK_HALVING)
+        block_rewards(self.total_blocks_mined())
+    }
+
+    pub fn emission_info(&self, average_block_time_secs: Option<u64>) -> EmissionInfo {
+        let total_blocks_mined = self.total_blocks_mined();
+        let blocks_until_next_halving = BLOCK_HALVING - total_blocks_mined % BLOCK_HALVING;
+        EmissionInfo {
+            emitted_supply: emitted_supply(total_blocks_mined),
+            max_supply: max_supply(),
+            current_epoch: total_blocks_mined / BLOCK_HALVING,
+            current_rewards: self.current_rewards(),
+            blocks_until_next_halving,
+            average_block_time_secs,
+            estimated_next_halving_ts: average_block_time_secs.map(|block_time| {
+                self.last_solved_challenge_ts
+                    .saturating_add(blocks_until_next_halving * block_time * SEC_NANOS)
+            }),
+        }
     }
 
     pub fn time_since_last_block(&self) -> u64 {
@@ -424,6 +833,32 @@ impl State {
             .or_insert(1);
         self.last_solved_challenge_ts = ic_cdk::api::time();
         self.miner_to_burned_cycles = BTreeMap::default();
+        self.miner_submissions = BTreeMap::default();
+    }
+
+    /// Checks a solution to the open challenge and awards the block to the
+    /// miner if it is valid. The caller closes the challenge on success.
+    pub fn solution_submitted(
+        &mut self,
+        miner: Principal,
+        owner: Principal,
+        open_challenge: Option<&Challenge>,
+        challenge: [u8; 32],
+        nonce: u64,
+    ) -> Result<(), String> {
+        let open_challenge = open_challenge.ok_or("no open challenge")?;
+        if open_challenge.value != challenge {
+            return Err("challenge already solved".to_string());
+        }
+        if leading_zero_bits(&challenge_hash(&challenge, &miner, nonce)) < open_challenge.difficulty
+        {
+            return Err("invalid solution".to_string());
+        }
+
+        let total_cycles_burned = self.miner_to_burned_cycles.values().sum();
+        let cycles_burned = *self.miner_to_burned_cycles.get(&miner).unwrap_or(&0);
+        self.challenge_solved(miner, owner, total_cycles_burned, cycles_burned);
+        Ok(())
     }
 }
 
@@ -1,23 +1,31 @@
 use bob_minter_v2::guard::GuardPrincipal;
 use bob_minter_v2::memory::{
-    get_block, get_block_to_mine, get_expiration, get_miner_owner, get_miner_to_owner_and_index,
-    get_user_expiration, insert_block_index, insert_expiration, insert_new_miner, is_known_block,
-    mined_block_count, user_count,
-};
-use bob_minter_v2::miner::{
-    create_canister, install_code, reinstall_code, start_canister, stop_canister,
+    append_miner_wasm_chunk, get_auto_top_up, get_block, get_block_to_mine,
+    get_current_miner_wasm_version, get_expiration, get_miner_owner, get_miner_refills,
+    get_miner_to_owner_and_index, get_miner_wasm_infos, get_miner_wasm_upload,
+    get_miner_wasm_version, get_user_expiration, insert_block_index, insert_expiration,
+    insert_miner_wasm, insert_new_miner, is_known_block, is_known_miner_wasm_version,
+    mined_block_count, mutate_submission_metrics, remove_miner_wasm_upload, set_auto_top_up,
+    set_challenge, set_config, set_current_miner_wasm_version, set_miner_rollout,
+    set_miner_wasm_version, user_count,
 };
+use bob_minter_v2::miner::{create_canister, install_code};
 use bob_minter_v2::tasks::{schedule_after, schedule_now, TaskType};
 use bob_minter_v2::{
-    fetch_block, miner_wasm, mutate_state, notify_top_up, read_state, replace_state, Block, State,
-    Stats, BLOCK_HALVING, DAY_NANOS, SEC_NANOS,
+    average_block_time_secs, check_miner_permission, fetch_block, is_rollout_active,
+    load_miner_wasm, miner_canister_settings, miner_wasm, mutate_state, new_miner_rollout,
+    notify_top_up, read_state, replace_state, sha256_hex, top_up_from, validate_config, AutoTopUp,
+    Block, BlockSelection, Challenge, Config, CurrentBlockStatus, EmissionInfo, LeaderBoardEntry,
+    Miner, MinerRefill, MinerRollout, MinerRolloutArgs, MinerWasmVersion, OperatorPermission,
+    Payment, PoolStats, State, Stats, SubmissionMetrics, BLOCK_HALVING, CYCLES_FOR_CREATION,
+    CYCLES_PAYMENT_BLOCK_INDEX, CYCLES_PER_POOL_DAY, DAY_NANOS, EMBEDDED_MINER_WASM_VERSION,
+    ICP_PER_MINER, ICP_TRANSFER_FEE, MIN_AUTO_TOP_UP_E8S, SEC_NANOS, TOP_UP_ACCOUNT, TOP_UP_MEMO,
 };
-use candid::{CandidType, Encode, Principal};
-use ic_cdk::{init, post_upgrade, query, update};
+use candid::{Encode, Principal};
+use ic_cdk::{init, inspect_message, post_upgrade, query, update};
 use icp_ledger::{AccountIdentifier, Operation};
 use std::time::Duration;
 
-
 fn main() {}
 
 #[post_upgrade]
@@ -42,13 +50,19 @@ fn post_upgrade() {
 
     replace_state(state);
     setup_timer();
+
+    // Resume the rollout interrupted by the upgrade.
+    if bob_minter_v2::memory::get_miner_rollout().is_some_and(|rollout| is_rollout_active(&rollout))
+    {
+        schedule_now(TaskType::UpgradeMiners);
+    }
 }
 
 #[init]
 fn init() {
     let state = State::new(ic_cdk::api::time());
 
-    let pool_id = Principal::from_text("zje3u-qaaaa-aaaai-acr2a-cai").unwrap();
+    let pool_id = POOL_CANISTER_ID;
     insert_new_miner(pool_id, pool_id, 0);
 
     replace_state(state);
@@ -58,6 +72,7 @@ fn init() {
 fn setup_timer() {
     schedule_now(TaskType::MineBob);
     schedule_after(Duration::from_secs(300), TaskType::ProcessLogic);
+    schedule_now(TaskType::TopUpMiners);
 }
 
 #[query]
@@ -90,12 +105,6 @@ fn get_latest_blocks() -> Vec<Block> {
     result
 }
 
-#[derive(CandidType)]
-struct CurrentBlockStatus {
-    active_miners: usize,
-    burned_cyles: u64,
-}
-
 #[query]
 fn get_current_block_status() -> CurrentBlockStatus {
     read_state(|s| CurrentBlockStatus {
@@ -104,13 +113,6 @@ fn get_current_block_status() -> CurrentBlockStatus {
     })
 }
 
-#[derive(CandidType, Ord, PartialOrd, Eq, PartialEq, Clone)]
-struct LeaderBoardEntry {
-    block_count: u64,
-    miner_count: usize,
-    owner: Principal,
-}
-
 #[query]
 fn get_leader_board() -> Vec<LeaderBoardEntry> {
     use std::collections::BTreeSet;
@@ -131,100 +133,176 @@ fn get_leader_board() -> Vec<LeaderBoardEntry> {
     result.iter().rev().take(20).cloned().collect()
 }
 
- #[update]
- async fn spawn_miner(block_index: u64) -> Result<Principal, String> {
-     // Transfer ICP to 6b896884e0b42634eca9c68c435c47b0ef2b97cf874a17198856b9c4efe89249
-     // With Memo 1347768404
-     if ic_cdk::caller() == Principal::anonymous() {
-         return Err("cannot spawn anonymously".to_string());
-     }
-     let _guard_principal = GuardPrincipal::new(ic_cdk::caller())
-         .map_err(|guard_error| format!("{:?}", guard_error))?;
-
-     if read_state(|s| s.miner_block_index.contains(&block_index)) || is_known_block(block_index) {
-         return Err("already consumed block index".to_string());
-     }
-
-     let transaction = fetch_block(block_index).await?.transaction;
-
-     if transaction.memo != icp_ledger::Memo(1347768404) {
-         return Err("unknown memo".to_string());
-     }
-
-     let caller = AccountIdentifier::new(ic_types::PrincipalId(ic_cdk::caller()), None);
-     let expect_to = AccountIdentifier::from_hex(
-         "e7b583c3e3e2837c987831a97a6b980cbb0be89819e85915beb3c02006923fce",
-     )
-     .unwrap();
-     let old_to = AccountIdentifier::from_hex(
-         "6b896884e0b42634eca9c68c435c47b0ef2b97cf874a17198856b9c4efe89249",
-     )
-     .unwrap();
-
-     if let Operation::Transfer {
-         from, to, amount, ..
-     } = transaction.operation
-     {
-         assert_eq!(from, caller, "unexpected caller");
-         if to != expect_to && to != old_to {
-             panic!("unexpected destintaion");
-         }
-         assert!(
-             amount >= icp_ledger::Tokens::from_e8s(99_990_000_u64),
-             "unexpected amount"
-         );
-     } else {
-         return Err("expected transfer".to_string());
-     }
-
-     const CYCLES_FOR_CREATION: u64 = 2_500_000_000_000;
-
-     let _res = notify_top_up(block_index).await?;
-
-     let arg = Encode!(&ic_cdk::caller()).unwrap();
-
-     let canister_id = create_canister(CYCLES_FOR_CREATION)
-         .await
-         .map_err(|e| format!("{} - {:?}", e.method, e.reason))?;
-
-     install_code(canister_id, miner_wasm().to_vec(), arg)
-         .await
-         .map_err(|e| format!("{} - {:?}", e.method, e.reason))?;
-
-     mutate_state(|s| {
-         s.new_miner(canister_id, ic_cdk::caller(), block_index);
-     });
-
-     insert_new_miner(canister_id, ic_cdk::caller(), block_index);
-
-     Ok(canister_id)
- }
+#[update]
+async fn spawn_miner(block_index: u64) -> Result<Principal, String> {
+    // Transfer ICP to 6b896884e0b42634eca9c68c435c47b0ef2b97cf874a17198856b9c4efe89249
+    // With Memo 1347768404
+    if ic_cdk::caller() == Principal::anonymous() {
+        return Err("cannot spawn anonymously".to_string());
+    }
+    let _guard_principal = GuardPrincipal::new(ic_cdk::caller())
+        .map_err(|guard_error| format!("{:?}", guard_error))?;
+
+    let amount = check_spawn_transfer(block_index).await?;
+    assert!(
+        amount >= icp_ledger::Tokens::from_e8s(99_990_000_u64),
+        "unexpected amount"
+    );
 
+    let _res = notify_top_up(block_index, ic_cdk::id()).await?;
+
+    install_miner(ic_cdk::caller(), block_index).await
+}
 
 #[update]
-async fn join_pool(block_index: u64) -> Result<(), String> {
+async fn spawn_miners(
+    block_index: u64,
+    count: u64,
+) -> Result<Vec<Result<Principal, String>>, String> {
+    // Transfer `count` ICP to e7b583c3e3e2837c987831a97a6b980cbb0be89819e85915beb3c02006923fce
+    // With Memo 1347768404
+    const MAX_MINERS_PER_SPAWN: u64 = 20;
+
     if ic_cdk::caller() == Principal::anonymous() {
         return Err("cannot spawn anonymously".to_string());
     }
+    if count == 0 || count > MAX_MINERS_PER_SPAWN {
+        return Err(format!(
+            "can spawn between 1 and {MAX_MINERS_PER_SPAWN} miners at once"
+        ));
+    }
     let _guard_principal = GuardPrincipal::new(ic_cdk::caller())
         .map_err(|guard_error| format!("{:?}", guard_error))?;
 
+    let amount = check_spawn_transfer(block_index).await?;
+    let expected_amount = count * ICP_PER_MINER - ICP_TRANSFER_FEE;
+    if amount < icp_ledger::Tokens::from_e8s(expected_amount) {
+        return Err(format!(
+            "amount too low, expected {expected_amount} e8s for {count} miners"
+        ));
+    }
+
+    let _res = notify_top_up(block_index, ic_cdk::id()).await?;
+
+    let mut result = vec![];
+    for _ in 0..count {
+        result.push(install_miner(ic_cdk::caller(), block_index).await);
+    }
+    Ok(result)
+}
+
+/// Checks that the transfer at `block_index` is an unconsumed top-up of this
+/// canister made by the caller and returns the transferred amount.
+async fn check_spawn_transfer(block_index: u64) -> Result<icp_ledger::Tokens, String> {
     if read_state(|s| s.miner_block_index.contains(&block_index)) || is_known_block(block_index) {
         return Err("already consumed block index".to_string());
     }
 
     let transaction = fetch_block(block_index).await?.transaction;
 
-    if transaction.memo != icp_ledger::Memo(1347768404) {
+    if transaction.memo != icp_ledger::Memo(TOP_UP_MEMO) {
         return Err("unknown memo".to_string());
     }
 
     let caller = AccountIdentifier::new(ic_types::PrincipalId(ic_cdk::caller()), None);
-    let expect_to = AccountIdentifier::from_hex(
-        "e7b583c3e3e2837c987831a97a6b980cbb0be89819e85915beb3c02006923fce",
+    let expect_to = AccountIdentifier::from_hex(TOP_UP_ACCOUNT).unwrap();
+    let old_to = AccountIdentifier::from_hex(
+        "6b896884e0b42634eca9c68c435c47b0ef2b97cf874a17198856b9c4efe89249",
     )
     .unwrap();
 
+    if let Operation::Transfer {
+        from, to, amount, ..
+    } = transaction.operation
+    {
+        assert_eq!(from, caller, "unexpected caller");
+        if to != expect_to && to != old_to {
+            panic!("unexpected destintaion");
+        }
+        Ok(amount)
+    } else {
+        Err("expected transfer".to_string())
+    }
+}
+
+#[update]
+async fn spawn_miner_with_payment(payment: Payment) -> Result<Principal, String> {
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return Err("cannot spawn anonymously".to_string());
+    }
+    let _guard_principal =
+        GuardPrincipal::new(caller).map_err(|guard_error| format!("{:?}", guard_error))?;
+
+    match payment {
+        Payment::Icrc2 { amount } => {
+            if amount < ICP_PER_MINER {
+                return Err(format!("amount too low, expected {ICP_PER_MINER} e8s"));
+            }
+            let block_index = top_up_from(caller, amount, ic_cdk::id()).await?;
+            let _res = notify_top_up(block_index, ic_cdk::id()).await?;
+            install_miner(caller, block_index).await
+        }
+        Payment::Cycles => {
+            let price = CYCLES_FOR_CREATION as u128;
+            if ic_cdk::api::call::msg_cycles_available128() < price {
+                return Err(format!("not enough cycles attached, expected {price}"));
+            }
+            // The cycles go back to the caller unless the miner is installed.
+            let miner = install_miner(caller, CYCLES_PAYMENT_BLOCK_INDEX).await?;
+            ic_cdk::api::call::msg_cycles_accept128(price);
+            Ok(miner)
+        }
+    }
+}
+
+async fn install_miner(owner: Principal, block_index: u64) -> Result<Principal, String> {
+    let arg = Encode!(&owner).unwrap();
+    let version = get_current_miner_wasm_version();
+    let wasm =
+        load_miner_wasm(version).ok_or_else(|| format!("unknown miner wasm version {version}"))?;
+
+    let settings = miner_canister_settings(&bob_minter_v2::memory::get_config(), owner);
+    let canister_id = create_canister(CYCLES_FOR_CREATION, settings)
+        .await
+        .map_err(|e| format!("{} - {:?}", e.method, e.reason))?;
+
+    install_code(canister_id, wasm.to_vec(), arg)
+        .await
+        .map_err(|e| format!("{} - {:?}", e.method, e.reason))?;
+
+    mutate_state(|s| {
+        s.new_miner(canister_id, owner, block_index);
+    });
+
+    insert_new_miner(canister_id, owner, block_index);
+    set_miner_wasm_version(canister_id, version);
+
+    Ok(canister_id)
+}
+
+#[update]
+async fn join_pool(block_index: u64) -> Result<(), String> {
+    if ic_cdk::caller() == Principal::anonymous() {
+        return Err("cannot spawn anonymously".to_string());
+    }
+    let _guard_principal = GuardPrincipal::new(ic_cdk::caller())
+        .map_err(|guard_error| format!("{:?}", guard_error))?;
+    check_pool_open()?;
+
+    if read_state(|s| s.miner_block_index.contains(&block_index)) || is_known_block(block_index) {
+        return Err("already consumed block index".to_string());
+    }
+
+    let transaction = fetch_block(block_index).await?.transaction;
+
+    if transaction.memo != icp_ledger::Memo(TOP_UP_MEMO) {
+        return Err("unknown memo".to_string());
+    }
+
+    let caller = AccountIdentifier::new(ic_types::PrincipalId(ic_cdk::caller()), None);
+    let expect_to = AccountIdentifier::from_hex(TOP_UP_ACCOUNT).unwrap();
+
     if let Operation::Transfer {
         from, to, amount, ..
     } = transaction.operation
@@ -238,17 +316,10 @@ async fn join_pool(block_index: u64) -> Result<(), String> {
             "amount too low"
         );
 
-        let _res = notify_top_up(block_index).await?;
+        let _res = notify_top_up(block_index, ic_cdk::id()).await?;
 
-        let caller = ic_cdk::caller();
-        let from_time = if let Some(time) = get_expiration(caller) {
-            time
-        } else {
-            ic_cdk::api::time()
-        };
         let days = amount.get_e8s() / 100_000_000;
-        let expire_at = from_time + days * DAY_NANOS;
-        insert_expiration(caller, expire_at);
+        extend_pool_membership(ic_cdk::caller(), days);
         insert_block_index(block_index);
         Ok(())
     } else {
@@ -256,18 +327,173 @@ async fn join_pool(block_index: u64) -> Result<(), String> {
     }
 }
 
+#[update]
+async fn join_pool_with_payment(payment: Payment) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    if caller == Principal::anonymous() {
+        return Err("cannot spawn anonymously".to_string());
+    }
+    let _guard_principal =
+        GuardPrincipal::new(caller).map_err(|guard_error| format!("{:?}", guard_error))?;
+    check_pool_open()?;
+
+    match payment {
+        Payment::Icrc2 { amount } => {
+            let days = amount / ICP_PER_MINER;
+            if days == 0 {
+                return Err(format!("amount too low, expected {ICP_PER_MINER} e8s"));
+            }
+            let block_index = top_up_from(caller, amount, ic_cdk::id()).await?;
+            let _res = notify_top_up(block_index, ic_cdk::id()).await?;
+            extend_pool_membership(caller, days);
+            insert_block_index(block_index);
+        }
+        Payment::Cycles => {
+            let days = (ic_cdk::api::call::msg_cycles_available128() / CYCLES_PER_POOL_DAY) as u64;
+            if days == 0 {
+                return Err(format!(
+                    "not enough cycles attached, expected {CYCLES_PER_POOL_DAY} per day"
+                ));
+            }
+            ic_cdk::api::call::msg_cycles_accept128(days as u128 * CYCLES_PER_POOL_DAY);
+            extend_pool_membership(caller, days);
+        }
+    }
+    Ok(())
+}
+
+/// The native pool burns cycles but cannot solve hash challenges.
+fn check_pool_open() -> Result<(), String> {
+    if bob_minter_v2::memory::get_config().block_selection == BlockSelection::HashChallenge {
+        return Err("the native pool is closed in the hash challenge mode".to_string());
+    }
+    Ok(())
+}
+
+fn extend_pool_membership(user: Principal, days: u64) {
+    let from_time = if let Some(time) = get_expiration(user) {
+        time
+    } else {
+        ic_cdk::api::time()
+    };
+    let expire_at = from_time + days * DAY_NANOS;
+    insert_expiration(user, expire_at);
+}
+
 #[update]
 async fn upgrade_miner(miner: Principal) -> Result<(), String> {
-    if let Some(owner) = get_miner_owner(miner) {
-        assert_eq!(ic_cdk::caller(), owner);
-        stop_canister(miner).await.map_err(|e| format!("{e:?}"))?;
-        reinstall_code(miner, miner_wasm().to_vec(), Encode!(&owner).unwrap())
-            .await
-            .map_err(|e| format!("{e:?}"))?;
-        start_canister(miner).await.map_err(|e| format!("{e:?}"))?;
-        return Ok(());
+    let owner = get_miner_owner(miner).ok_or("unknown miner")?;
+    check_miner_permission(miner, ic_cdk::caller(), OperatorPermission::Upgrade).await?;
+    bob_minter_v2::upgrade_miner(miner, owner, get_current_miner_wasm_version()).await
+}
+
+/// Tops up the miner from the ICP allowance the owner granted to this
+/// canister whenever its cycle balance drops below the threshold. Passing
+/// `None` disables the auto top-up.
+#[update]
+async fn set_miner_auto_top_up(
+    miner: Principal,
+    auto_top_up: Option<AutoTopUp>,
+) -> Result<(), String> {
+    check_miner_permission(miner, ic_cdk::caller(), OperatorPermission::TopUp).await?;
+    if let Some(auto_top_up) = &auto_top_up {
+        if auto_top_up.amount_e8s < MIN_AUTO_TOP_UP_E8S {
+            return Err(format!(
+                "top-up amount must be at least {MIN_AUTO_TOP_UP_E8S} e8s"
+            ));
+        }
+    }
+    let enabled = auto_top_up.is_some();
+    set_auto_top_up(miner, auto_top_up);
+    if enabled {
+        schedule_now(TaskType::TopUpMiners);
+    }
+    Ok(())
+}
+
+#[query]
+fn get_miner_auto_top_up(miner: Principal) -> Option<AutoTopUp> {
+    get_auto_top_up(miner)
+}
+
+#[query]
+fn get_miner_refill_log(miner: Principal) -> Vec<MinerRefill> {
+    get_miner_refills(miner)
+}
+
+#[update]
+fn upload_miner_wasm_chunk(version: u64, chunk: Vec<u8>) -> Result<u64, String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("only controllers can upload miner wasms".to_string());
+    }
+    if version == EMBEDDED_MINER_WASM_VERSION || is_known_miner_wasm_version(version) {
+        return Err(format!("miner wasm version {version} already exists"));
+    }
+    Ok(append_miner_wasm_chunk(version, chunk))
+}
+
+#[update]
+fn commit_miner_wasm(version: u64, sha256: String) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("only controllers can upload miner wasms".to_string());
+    }
+    if version == EMBEDDED_MINER_WASM_VERSION || is_known_miner_wasm_version(version) {
+        return Err(format!("miner wasm version {version} already exists"));
+    }
+    let wasm = get_miner_wasm_upload(version)
+        .ok_or_else(|| format!("no upload for miner wasm version {version}"))?;
+    let actual_sha256 = sha256_hex(&wasm);
+    if actual_sha256 != sha256.to_lowercase() {
+        return Err(format!(
+            "sha256 mismatch, expected {sha256} got {actual_sha256}"
+        ));
     }
-    Err("unknown miner".to_string())
+    remove_miner_wasm_upload(version);
+    insert_miner_wasm(
+        MinerWasmVersion {
+            version,
+            sha256: actual_sha256,
+            size: wasm.len() as u64,
+            uploaded_at: ic_cdk::api::time(),
+        },
+        wasm,
+    );
+    Ok(())
+}
+
+#[query]
+fn get_miner_wasm_versions() -> Vec<MinerWasmVersion> {
+    let embedded_wasm = miner_wasm();
+    let mut versions = vec![MinerWasmVersion {
+        version: EMBEDDED_MINER_WASM_VERSION,
+        sha256: sha256_hex(&embedded_wasm),
+        size: embedded_wasm.len() as u64,
+        uploaded_at: 0,
+    }];
+    versions.extend(get_miner_wasm_infos());
+    versions
+}
+
+#[update]
+fn start_miner_rollout(args: MinerRolloutArgs) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("only controllers can start a miner rollout".to_string());
+    }
+    if args.version != EMBEDDED_MINER_WASM_VERSION && !is_known_miner_wasm_version(args.version) {
+        return Err(format!("unknown miner wasm version {}", args.version));
+    }
+    if args.wave_size == 0 {
+        return Err("wave size must be positive".to_string());
+    }
+    set_current_miner_wasm_version(args.version);
+    set_miner_rollout(new_miner_rollout(args, ic_cdk::api::time()));
+    schedule_now(TaskType::UpgradeMiners);
+    Ok(())
+}
+
+#[query]
+fn get_miner_rollout() -> Option<MinerRollout> {
+    bob_minter_v2::memory::get_miner_rollout()
 }
 
 #[export_name = "canister_global_timer"]
@@ -275,31 +501,108 @@ fn timer() {
     bob_minter_v2::timer();
 }
 
+#[inspect_message]
+fn inspect_message() {
+    // Only ingress messages go through this hook, miners are canisters.
+    let method = ic_cdk::api::call::method_name();
+    if (method == "submit_burned_cycles" || method == "submit_solution")
+        && !read_state(|s| s.miner_to_owner.contains_key(&ic_cdk::caller()))
+    {
+        return;
+    }
+    ic_cdk::api::call::accept_message();
+}
+
 #[update]
 fn submit_burned_cycles(cycles: u64) -> Result<(), String> {
     let _guard_principal = GuardPrincipal::new(ic_cdk::caller())
         .map_err(|guard_error| format!("{:?}", guard_error))?;
 
-    if !read_state(|s| s.miner_to_owner.contains_key(&ic_cdk::caller())) {
+    let caller = ic_cdk::caller();
+    let now = ic_cdk::api::time();
+    let config = bob_minter_v2::memory::get_config();
+
+    if !read_state(|s| s.miner_to_owner.contains_key(&caller)) {
+        mutate_submission_metrics(|m| m.rejected_unregistered += 1);
         return Err(
             "Unregitered miner, only miner spawned from this canister are allowed to submit"
                 .to_string(),
         );
     }
 
+    if let Err(e) = read_state(|s| s.check_submission_rate(caller, now, &config)) {
+        mutate_submission_metrics(|m| m.rejected_rate_limited += 1);
+        return Err(e);
+    }
+
+    // Miners attach the burned cycles to the call so that the minter burns
+    // them itself, reported counts are only trusted on the legacy path.
+    let attached_cycles = ic_cdk::api::call::msg_cycles_available128();
+    let cycles = if attached_cycles > 0 {
+        attached_cycles.min(u64::MAX as u128) as u64
+    } else if config.accept_unverified_burns {
+        cycles
+    } else {
+        mutate_submission_metrics(|m| m.rejected_unverified += 1);
+        return Err("Burned cycles must be attached to the call".to_string());
+    };
+
     if cycles < 1_000_000_000 {
+        mutate_submission_metrics(|m| m.rejected_not_enough_cycles += 1);
         return Err("Not enough cycle burned".to_string());
     }
 
+    if attached_cycles > 0 {
+        let accepted = ic_cdk::api::call::msg_cycles_accept128(cycles as u128);
+        ic_cdk::api::cycles_burn(accepted);
+    }
+
+    mutate_state(|s| s.record_submission(caller, cycles, now));
+    mutate_submission_metrics(|m| m.accepted += 1);
+
+    Ok(())
+}
+
+#[update]
+fn submit_solution(challenge: [u8; 32], nonce: u64) -> Result<(), String> {
     let caller = ic_cdk::caller();
+    let owner = get_miner_owner(caller)
+        .ok_or("Unregitered miner, only miner spawned from this canister are allowed to submit")?;
+    if bob_minter_v2::memory::get_config().block_selection != BlockSelection::HashChallenge {
+        return Err("the hash challenge mode is disabled".to_string());
+    }
 
+    let open_challenge = bob_minter_v2::memory::get_challenge();
     mutate_state(|s| {
-        s.miner_to_burned_cycles
-            .entry(caller)
-            .and_modify(|e| *e += cycles)
-            .or_insert(cycles);
-    });
+        s.solution_submitted(caller, owner, open_challenge.as_ref(), challenge, nonce)
+    })?;
+    set_challenge(None);
+    schedule_now(TaskType::MineBob);
+    Ok(())
+}
+
+#[query]
+fn get_challenge() -> Option<Challenge> {
+    bob_minter_v2::memory::get_challenge()
+}
+
+#[query]
+fn get_submission_metrics() -> SubmissionMetrics {
+    bob_minter_v2::memory::get_submission_metrics()
+}
+
+#[query]
+fn get_config() -> Config {
+    bob_minter_v2::memory::get_config()
+}
 
+#[update]
+fn update_config(config: Config) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return Err("only controllers can update the config".to_string());
+    }
+    validate_config(&config)?;
+    set_config(config);
     Ok(())
 }
 
@@ -316,15 +619,15 @@ fn get_statistics() -> Stats {
     })
 }
 
-#[derive(CandidType)]
-struct PoolStats {
-    pool_mined_blocks: u64,
-    users_count_in_pool: u64,
+#[query]
+fn get_emission_info() -> EmissionInfo {
+    let average_block_time_secs = average_block_time_secs();
+    read_state(|s| s.emission_info(average_block_time_secs))
 }
 
 #[query]
 fn get_pool_statistic() -> PoolStats {
-    let pool_id = Principal::from_text("zje3u-qaaaa-aaaai-acr2a-cai").unwrap();
+    let pool_id = POOL_CANISTER_ID;
 
     read_state(|s| PoolStats {
         pool_mined_blocks: *s.miner_to_mined_block.get(&pool_id).unwrap_or(&0),
@@ -340,12 +643,6 @@ fn hours_left_in_pool(maybe_target: Option<Principal>) -> u64 {
     expiration.saturating_sub(now) / (60 * 60 * SEC_NANOS)
 }
 
-#[derive(CandidType)]
-struct Miner {
-    pub id: Principal,
-    pub mined_blocks: u64,
-}
-
 #[query]
 fn get_miners(of: Principal) -> Vec<Miner> {
     read_state(|s| {
@@ -360,6 +657,7 @@ fn get_miners(of: Principal) -> Vec<Miner> {
             result.push(Miner {
                 id: miner,
                 mined_blocks,
+                wasm_version: get_miner_wasm_version(miner),
             });
         }
         result
@@ -386,4 +684,12 @@ mod tests {
         let result = service_equal(declared_interface, implemented_interface);
         assert!(result.is_ok(), "{:?}\n\n", result.unwrap_err());
     }
+
+    #[test]
+    fn should_decode_the_pool_canister_id() {
+        assert_eq!(
+            POOL_CANISTER_ID,
+            Principal::from_text("zje3u-qaaaa-aaaai-acr2a-cai").unwrap()
+        );
+    }
 }
@@ -1,9 +1,14 @@
-use crate::Block;
+use crate::{
+    AutoTopUp, Block, Challenge, Config, MinerRefill, MinerRollout, MinerWasmVersion,
+    SubmissionMetrics, EMBEDDED_MINER_WASM_VERSION,
+};
 use candid::Principal;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager as MM, VirtualMemory};
 use ic_stable_structures::storable::Bound;
 use ic_stable_structures::DefaultMemoryImpl;
-use ic_stable_structures::{DefaultMemoryImpl as DefMem, StableBTreeMap, StableLog, Storable};
+use ic_stable_structures::{
+    DefaultMemoryImpl as DefMem, StableBTreeMap, StableCell, StableLog, Storable,
+};
 use std::borrow::Cow;
 use std::cell::RefCell;
 
@@ -37,6 +42,20 @@ const LOG_DATA_MEM_ID: MemoryId = MemoryId::new(2);
 const BLOCKS_TO_MINE_ID: MemoryId = MemoryId::new(3);
 const USER_TO_EXPIRATION_ID: MemoryId = MemoryId::new(4);
 const KNOWN_BLOCK_INDEX_ID: MemoryId = MemoryId::new(5);
+const CONFIG_ID: MemoryId = MemoryId::new(6);
+const MINER_WASMS_ID: MemoryId = MemoryId::new(7);
+const MINER_WASM_INFOS_ID: MemoryId = MemoryId::new(8);
+const MINER_WASM_UPLOADS_ID: MemoryId = MemoryId::new(9);
+const MINER_TO_WASM_VERSION_ID: MemoryId = MemoryId::new(10);
+const CURRENT_MINER_WASM_VERSION_ID: MemoryId = MemoryId::new(11);
+const MINER_TO_AUTO_TOP_UP_ID: MemoryId = MemoryId::new(12);
+const MINER_REFILLS_ID: MemoryId = MemoryId::new(13);
+const SUBMISSION_METRICS_ID: MemoryId = MemoryId::new(14);
+const MINER_ROLLOUT_ID: MemoryId = MemoryId::new(15);
+const CHALLENGE_ID: MemoryId = MemoryId::new(16);
+
+// Number of refills kept per miner, the oldest are dropped first.
+const MAX_REFILLS_PER_MINER: usize = 100;
 
 type VM = VirtualMemory<DefMem>;
 
@@ -72,6 +91,96 @@ thread_local! {
         MEMORY_MANAGER.with(|mm| {
         RefCell::new(StableBTreeMap::init(mm.borrow().get(KNOWN_BLOCK_INDEX_ID)))
         });
+
+    static CONFIG: RefCell<StableCell<Cbor<Config>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableCell::init(mm.borrow().get(CONFIG_ID), Cbor(Config::default()))
+            .expect("failed to initialize the config"))
+        });
+
+    static MINER_WASMS: RefCell<StableBTreeMap<u64, Vec<u8>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableBTreeMap::init(mm.borrow().get(MINER_WASMS_ID)))
+        });
+
+    static MINER_WASM_INFOS: RefCell<StableBTreeMap<u64, Cbor<MinerWasmVersion>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableBTreeMap::init(mm.borrow().get(MINER_WASM_INFOS_ID)))
+        });
+
+    // Chunks of the pending uploads, keyed by version and offset.
+    static MINER_WASM_UPLOADS: RefCell<StableBTreeMap<(u64, u64), Vec<u8>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableBTreeMap::init(mm.borrow().get(MINER_WASM_UPLOADS_ID)))
+        });
+
+    static MINER_TO_WASM_VERSION: RefCell<StableBTreeMap<Principal, u64, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableBTreeMap::init(mm.borrow().get(MINER_TO_WASM_VERSION_ID)))
+        });
+
+    static CURRENT_MINER_WASM_VERSION: RefCell<StableCell<u64, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableCell::init(mm.borrow().get(CURRENT_MINER_WASM_VERSION_ID), 0)
+            .expect("failed to initialize the current miner wasm version"))
+        });
+
+    static MINER_TO_AUTO_TOP_UP: RefCell<StableBTreeMap<Principal, Cbor<AutoTopUp>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableBTreeMap::init(mm.borrow().get(MINER_TO_AUTO_TOP_UP_ID)))
+        });
+
+    static MINER_REFILLS: RefCell<StableBTreeMap<(Principal, u64), Cbor<MinerRefill>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableBTreeMap::init(mm.borrow().get(MINER_REFILLS_ID)))
+        });
+
+    static SUBMISSION_METRICS: RefCell<StableCell<Cbor<SubmissionMetrics>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableCell::init(
+            mm.borrow().get(SUBMISSION_METRICS_ID),
+            Cbor(SubmissionMetrics::default()),
+        ).expect("failed to initialize the submission metrics"))
+        });
+
+    static MINER_ROLLOUT: RefCell<StableCell<Cbor<Option<MinerRollout>>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableCell::init(mm.borrow().get(MINER_ROLLOUT_ID), Cbor(None))
+            .expect("failed to initialize the miner rollout"))
+        });
+
+    static CHALLENGE: RefCell<StableCell<Cbor<Option<Challenge>>, VM>> =
+        MEMORY_MANAGER.with(|mm| {
+        RefCell::new(StableCell::init(mm.borrow().get(CHALLENGE_ID), Cbor(None))
+            .expect("failed to initialize the challenge"))
+        });
+}
+
+pub fn get_config() -> Config {
+    CONFIG.with(|c| c.borrow().get().0.clone())
+}
+
+pub fn set_config(config: Config) {
+    CONFIG
+        .with(|c| c.borrow_mut().set(Cbor(config)))
+        .expect("failed to set config");
+}
+
+pub fn get_submission_metrics() -> SubmissionMetrics {
+    SUBMISSION_METRICS.with(|m| m.borrow().get().0.clone())
+}
+
+pub fn mutate_submission_metrics<F>(f: F)
+where
+    F: FnOnce(&mut SubmissionMetrics),
+{
+    SUBMISSION_METRICS.with(|m| {
+        let mut metrics = m.borrow().get().0.clone();
+        f(&mut metrics);
+        m.borrow_mut()
+            .set(Cbor(metrics))
+            .expect("failed to set the submission metrics");
+    });
 }
 
 pub fn insert_block_to_mine(block: Block) {
@@ -167,3 +276,158 @@ pub fn is_known_block(block_index: u64) -> bool {
 pub fn insert_block_index(block_index: u64) {
     KNOWN_INDEX.with(|s| s.borrow_mut().insert(block_index, ()));
 }
+
+/// Appends a chunk to the pending upload of the given miner wasm version and
+/// returns the uploaded size so far.
+pub fn append_miner_wasm_chunk(version: u64, chunk: Vec<u8>) -> u64 {
+    MINER_WASM_UPLOADS.with(|s| {
+        let mut uploads = s.borrow_mut();
+        let offset = uploads
+            .range((version, 0)..=(version, u64::MAX))
+            .next_back()
+            .map(|((_, offset), last_chunk)| offset + last_chunk.len() as u64)
+            .unwrap_or(0);
+        let size = offset + chunk.len() as u64;
+        uploads.insert((version, offset), chunk);
+        size
+    })
+}
+
+/// Concatenates the uploaded chunks of the given miner wasm version.
+pub fn get_miner_wasm_upload(version: u64) -> Option<Vec<u8>> {
+    MINER_WASM_UPLOADS.with(|s| {
+        let uploads = s.borrow();
+        let mut chunks = uploads.range((version, 0)..=(version, u64::MAX)).peekable();
+        chunks.peek()?;
+        Some(chunks.flat_map(|(_, chunk)| chunk).collect())
+    })
+}
+
+pub fn remove_miner_wasm_upload(version: u64) {
+    MINER_WASM_UPLOADS.with(|s| {
+        let mut uploads = s.borrow_mut();
+        let keys: Vec<(u64, u64)> = uploads
+            .range((version, 0)..=(version, u64::MAX))
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            uploads.remove(&key);
+        }
+    });
+}
+
+pub fn insert_miner_wasm(info: MinerWasmVersion, wasm: Vec<u8>) {
+    let version = info.version;
+    MINER_WASMS.with(|s| s.borrow_mut().insert(version, wasm));
+    MINER_WASM_INFOS.with(|s| s.borrow_mut().insert(version, Cbor(info)));
+}
+
+pub fn get_miner_wasm(version: u64) -> Option<Vec<u8>> {
+    MINER_WASMS.with(|s| s.borrow().get(&version))
+}
+
+pub fn get_miner_wasm_infos() -> Vec<MinerWasmVersion> {
+    MINER_WASM_INFOS.with(|s| s.borrow().iter().map(|(_, info)| info.0).collect())
+}
+
+pub fn is_known_miner_wasm_version(version: u64) -> bool {
+    MINER_WASM_INFOS.with(|s| s.borrow().contains_key(&version))
+}
+
+pub fn set_miner_wasm_version(miner: Principal, version: u64) {
+    MINER_TO_WASM_VERSION.with(|s| s.borrow_mut().insert(miner, version));
+}
+
+/// Returns the wasm version installed on the miner, miners spawned before
+/// versioning run the embedded wasm.
+pub fn get_miner_wasm_version(miner: Principal) -> u64 {
+    MINER_TO_WASM_VERSION.with(|s| {
+        s.borrow()
+            .get(&miner)
+            .unwrap_or(EMBEDDED_MINER_WASM_VERSION)
+    })
+}
+
+pub fn get_current_miner_wasm_version() -> u64 {
+    CURRENT_MINER_WASM_VERSION.with(|s| *s.borrow().get())
+}
+
+pub fn set_current_miner_wasm_version(version: u64) {
+    CURRENT_MINER_WASM_VERSION
+        .with(|s| s.borrow_mut().set(version))
+        .expect("failed to set the current miner wasm version");
+}
+
+pub fn get_miner_rollout() -> Option<MinerRollout> {
+    MINER_ROLLOUT.with(|s| s.borrow().get().0.clone())
+}
+
+pub fn set_miner_rollout(rollout: MinerRollout) {
+    MINER_ROLLOUT
+        .with(|s| s.borrow_mut().set(Cbor(Some(rollout))))
+        .expect("failed to set the miner rollout");
+}
+
+/// Applies `f` to the ongoing rollout, if any.
+pub fn mutate_miner_rollout<F>(f: F)
+where
+    F: FnOnce(&mut MinerRollout),
+{
+    if let Some(mut rollout) = get_miner_rollout() {
+        f(&mut rollout);
+        set_miner_rollout(rollout);
+    }
+}
+
+/// Returns the open challenge of the hash challenge mode.
+pub fn get_challenge() -> Option<Challenge> {
+    CHALLENGE.with(|s| s.borrow().get().0.clone())
+}
+
+pub fn set_challenge(challenge: Option<Challenge>) {
+    CHALLENGE
+        .with(|s| s.borrow_mut().set(Cbor(challenge)))
+        .expect("failed to set the challenge");
+}
+
+pub fn set_auto_top_up(miner: Principal, auto_top_up: Option<AutoTopUp>) {
+    MINER_TO_AUTO_TOP_UP.with(|s| match auto_top_up {
+        Some(auto_top_up) => s.borrow_mut().insert(miner, Cbor(auto_top_up)),
+        None => s.borrow_mut().remove(&miner),
+    });
+}
+
+pub fn get_auto_top_up(miner: Principal) -> Option<AutoTopUp> {
+    MINER_TO_AUTO_TOP_UP.with(|s| s.borrow().get(&miner).map(|a| a.0))
+}
+
+pub fn get_auto_top_ups() -> Vec<(Principal, AutoTopUp)> {
+    MINER_TO_AUTO_TOP_UP.with(|s| s.borrow().iter().map(|(m, a)| (m, a.0)).collect())
+}
+
+pub fn push_miner_refill(miner: Principal, refill: MinerRefill) {
+    MINER_REFILLS.with(|s| {
+        let mut refills = s.borrow_mut();
+        refills.insert((miner, refill.timestamp), Cbor(refill));
+
+        let keys: Vec<(Principal, u64)> = refills
+            .range((miner, 0)..=(miner, u64::MAX))
+            .map(|(key, _)| key)
+            .collect();
+        let excess = keys.len().saturating_sub(MAX_REFILLS_PER_MINER);
+        for key in &keys[..excess] {
+            refills.remove(key);
+        }
+    });
+}
+
+/// Returns the refills of the miner, most recent first.
+pub fn get_miner_refills(miner: Principal) -> Vec<MinerRefill> {
+    MINER_REFILLS.with(|s| {
+        s.borrow()
+            .range((miner, 0)..=(miner, u64::MAX))
+            .map(|(_, refill)| refill.0)
+            .rev()
+            .collect()
+    })
+}
@@ -2,7 +2,7 @@ use candid::{CandidType, Principal};
 use ic_base_types::PrincipalId;
 use ic_cdk::api::call::RejectionCode;
 use ic_management_canister_types::{
-    CanisterIdRecord, CanisterInstallMode, CanisterSettingsArgsBuilder, CreateCanisterArgs,
+    CanisterIdRecord, CanisterInstallMode, CanisterSettingsArgs, CreateCanisterArgs,
     InstallCodeArgs,
 };
 use serde::de::DeserializeOwned;
@@ -109,6 +109,26 @@ pub async fn reinstall_code(
     Ok(())
 }
 
+pub async fn upgrade_code(
+    canister_id: Principal,
+    wasm_module: Vec<u8>,
+    arg: Vec<u8>,
+) -> Result<(), CallError> {
+    let install_code = InstallCodeArgs {
+        mode: CanisterInstallMode::Upgrade,
+        canister_id: PrincipalId::from(canister_id),
+        wasm_module,
+        arg,
+        compute_allocation: None,
+        memory_allocation: None,
+        sender_canister_version: None,
+    };
+
+    call("install_code", 0, &install_code).await?;
+
+    Ok(())
+}
+
 pub async fn stop_canister(canister_id: Principal) -> Result<(), CallError> {
     ic_cdk::api::management_canister::main::stop_canister(
         ic_cdk::api::management_canister::main::CanisterIdRecord { canister_id },
@@ -131,9 +151,25 @@ pub async fn start_canister(canister_id: Principal) -> Result<(), CallError> {
     })
 }
 
-pub async fn create_canister(cycles_for_canister_creation: u64) -> Result<Principal, CallError> {
+pub async fn canister_cycles(canister_id: Principal) -> Result<u128, CallError> {
+    let (status,) = ic_cdk::api::management_canister::main::canister_status(
+        ic_cdk::api::management_canister::main::CanisterIdRecord { canister_id },
+    )
+    .await
+    .map_err(|(code, msg)| CallError {
+        method: "canister_status".to_string(),
+        reason: Reason::from_reject(code, msg),
+    })?;
+
+    Ok(u128::try_from(status.cycles.0).unwrap_or(u128::MAX))
+}
+
+pub async fn create_canister(
+    cycles_for_canister_creation: u64,
+    settings: CanisterSettingsArgs,
+) -> Result<Principal, CallError> {
     let create_args = CreateCanisterArgs {
-        settings: Some(CanisterSettingsArgsBuilder::new().build()),
+        settings: Some(settings),
         ..Default::default()
     };
     let result: CanisterIdRecord = call(
@@ -15,6 +15,8 @@ thread_local! {
 pub enum TaskType {
     ProcessLogic,
     MineBob,
+    UpgradeMiners,
+    TopUpMiners,
 }
 
 #[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, CandidType)]
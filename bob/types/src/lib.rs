@@ -0,0 +1,5 @@
+//! Candid types of the BoB minter and miners, shared with their clients
+//! without pulling in the canisters themselves.
+
+pub mod miner;
+pub mod minter;
@@ -0,0 +1,105 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const DEFAULT_ROUND_LENGTH_SECS: u64 = 240;
+
+// Safety buffer of cycles kept on the miner balance to stay above the
+// freezing threshold, burns never dip into it.
+pub const DEFAULT_RESERVE_CYCLES: u128 = 100_000_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct Challenge {
+    pub value: [u8; 32],
+    pub difficulty: u64,
+    pub received_at: u64,
+    /// Next nonce to try, the search resumes from there.
+    pub next_nonce: u64,
+    /// Solution found but not yet accepted by the minter.
+    #[serde(default)]
+    pub solution: Option<u64>,
+}
+
+#[derive(Clone, Debug, CandidType, Serialize, Deserialize)]
+pub struct Round {
+    pub timestamp: u64,
+    pub cycles_burned: u128,
+    pub result: Result<(), String>,
+}
+
+/// Hours of the day (UTC) during which the miner burns cycles, from
+/// `start_hour` included to `end_hour` excluded. The range wraps around
+/// midnight if `end_hour` is smaller than `start_hour`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct ActiveHours {
+    pub start_hour: u8,
+    pub end_hour: u8,
+}
+
+/// Setting `target_daily_burn` to 0 disables it and setting active hours
+/// with the same start and end hour makes the miner active all day.
+#[derive(CandidType, Deserialize, Default)]
+pub struct MinerSettings {
+    pub max_cycles_per_round: Option<u128>,
+    pub new_owner: Option<Principal>,
+    pub round_length_secs: Option<u64>,
+    pub target_daily_burn: Option<u128>,
+    pub reserve_cycles: Option<u128>,
+    pub active_hours: Option<ActiveHours>,
+}
+
+/// Actions the owner of a miner can delegate to an operator.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, CandidType, Serialize, Deserialize,
+)]
+pub enum OperatorPermission {
+    /// Update the miner settings, except for the owner.
+    AdjustBurnRate,
+    /// Upgrade the miner through the minter.
+    Upgrade,
+    /// Configure the auto top-up of the miner on the minter.
+    TopUp,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct StatsV2 {
+    pub cycle_balance: u64,
+    pub cycles_burned_per_round: u128,
+    pub round_length_secs: u64,
+    pub last_round_cyles_burned: u128,
+    /// Days the miner can keep burning at the current pace before reaching
+    /// its cycle reserve.
+    pub runway_days: Option<u64>,
+}
+
+#[derive(Clone, CandidType, Serialize, Deserialize)]
+pub struct State {
+    pub bob_minter_id: Principal,
+    pub owner: Principal,
+    pub solved_challenges: u64,
+    pub hashes_computed: u128,
+    pub max_cycles_per_round: u128,
+    pub last_cycles_burned: u128,
+    #[serde(default = "default_round_length_secs")]
+    pub round_length_secs: u64,
+    #[serde(default)]
+    pub target_daily_burn: Option<u128>,
+    #[serde(default = "default_reserve_cycles")]
+    pub reserve_cycles: u128,
+    #[serde(default)]
+    pub active_hours: Option<ActiveHours>,
+    #[serde(default)]
+    pub rewards_won: u64,
+    #[serde(default)]
+    pub challenge: Option<Challenge>,
+    #[serde(default)]
+    pub operators: BTreeMap<Principal, BTreeSet<OperatorPermission>>,
+}
+
+fn default_round_length_secs() -> u64 {
+    DEFAULT_ROUND_LENGTH_SECS
+}
+
+fn default_reserve_cycles() -> u128 {
+    DEFAULT_RESERVE_CYCLES
+}
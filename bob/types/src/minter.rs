@@ -0,0 +1,192 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Default delay between two submissions of a miner. Miners can shorten their
+// rounds down to 120 seconds, the slack absorbs timers firing a bit early.
+const MIN_SUBMISSION_INTERVAL_SECS: u64 = 100;
+
+#[derive(Clone, CandidType, Ord, PartialOrd, Eq, PartialEq, Deserialize, Serialize, Debug)]
+pub struct Block {
+    pub to: Principal,
+    pub miner: Option<Principal>,
+    pub rewards: u64,
+    pub timestamp: u64,
+    pub total_cycles_burned: Option<u64>,
+    pub miner_cycles_burned: Option<u64>,
+    pub miner_count: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct CurrentBlockStatus {
+    pub active_miners: usize,
+    pub burned_cyles: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug, Ord, PartialOrd, Eq, PartialEq, Clone)]
+pub struct LeaderBoardEntry {
+    pub block_count: u64,
+    pub miner_count: usize,
+    pub owner: Principal,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct PoolStats {
+    pub pool_mined_blocks: u64,
+    pub users_count_in_pool: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct Miner {
+    pub id: Principal,
+    pub mined_blocks: u64,
+    pub wasm_version: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct Stats {
+    pub average_block_speed: u64,
+    pub block_count: u64,
+    pub miner_count: usize,
+    pub halving_count: u64,
+    pub cycle_balance: u64,
+    pub time_since_last_block: u64,
+    pub pending_blocks: Vec<Block>,
+}
+
+/// How the miner of each block is selected.
+#[derive(Clone, Copy, CandidType, Deserialize, Serialize, Debug, PartialEq, Eq, Default)]
+pub enum BlockSelection {
+    /// Miners win blocks with a probability proportional to the cycles
+    /// they burned.
+    #[default]
+    CycleBurnLottery,
+    /// The first miner to solve the SHA-256 challenge of the round wins
+    /// the block.
+    HashChallenge,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct Challenge {
+    pub value: [u8; 32],
+    /// Number of leading zero bits required in the solution hash.
+    pub difficulty: u64,
+    pub issued_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Config {
+    /// Credit the cycle count reported by miners that do not attach the
+    /// burned cycles to `submit_burned_cycles`. Deprecated, kept until all
+    /// miners are upgraded.
+    pub accept_unverified_burns: bool,
+    /// Maximum number of submissions accepted from a miner between two blocks.
+    pub max_submissions_per_round: u64,
+    /// Minimum delay between two submissions of the same miner.
+    pub min_submission_interval_secs: u64,
+    pub block_selection: BlockSelection,
+    /// Difficulty of the challenges in the hash challenge mode.
+    pub challenge_difficulty: u64,
+    /// Freezing threshold of the spawned miners.
+    pub miner_freezing_threshold_secs: u64,
+    /// Reserved cycles limit of the spawned miners.
+    pub miner_reserved_cycles_limit: u128,
+    /// Add the owner as a controller of the spawned miners, next to this
+    /// canister. Owners can then top up, inspect and reinstall their miners.
+    pub miner_owner_is_controller: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            accept_unverified_burns: true,
+            max_submissions_per_round: 4,
+            min_submission_interval_secs: MIN_SUBMISSION_INTERVAL_SECS,
+            block_selection: BlockSelection::CycleBurnLottery,
+            challenge_difficulty: 24,
+            miner_freezing_threshold_secs: 30 * 24 * 60 * 60,
+            miner_reserved_cycles_limit: 5_000_000_000_000,
+            miner_owner_is_controller: false,
+        }
+    }
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct AutoTopUp {
+    /// The miner is topped up once its cycle balance drops below this.
+    pub threshold_cycles: u128,
+    /// ICP pulled from the allowance of the owner on each top-up, in e8s.
+    pub amount_e8s: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize, Debug)]
+pub struct MinerRefill {
+    pub timestamp: u64,
+    pub amount_e8s: u64,
+    /// Cycle balance of the miner before the top-up.
+    pub cycle_balance: u128,
+    /// Cycles minted for the miner.
+    pub result: Result<u128, String>,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize, Debug)]
+pub struct MinerWasmVersion {
+    pub version: u64,
+    pub sha256: String,
+    pub size: u64,
+    pub uploaded_at: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize, Debug)]
+pub struct MinerRolloutArgs {
+    pub version: u64,
+    pub wave_size: u64,
+    /// The rollout halts once more miners than this failed to upgrade.
+    pub max_failures: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize, Debug)]
+pub struct MinerRollout {
+    pub version: u64,
+    pub wave_size: u64,
+    pub max_failures: u64,
+    pub started_at: u64,
+    pub completed_at: Option<u64>,
+    pub upgraded: u64,
+    pub failed: BTreeMap<Principal, String>,
+}
+
+#[derive(Clone, CandidType, Deserialize, Debug)]
+pub enum Payment {
+    /// ICRC-2 `transfer_from` of `amount` ICP e8s approved by the caller.
+    Icrc2 { amount: u64 },
+    /// Cycles attached to the call.
+    Cycles,
+}
+
+#[derive(Clone, Copy, Default, CandidType, Deserialize, Serialize, Debug)]
+pub struct MinerSubmissions {
+    pub count: u64,
+    pub last_submission_ts: u64,
+}
+
+#[derive(Clone, Default, CandidType, Deserialize, Serialize, Debug)]
+pub struct SubmissionMetrics {
+    pub accepted: u64,
+    pub rejected_unregistered: u64,
+    pub rejected_unverified: u64,
+    pub rejected_rate_limited: u64,
+    pub rejected_not_enough_cycles: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct EmissionInfo {
+    pub emitted_supply: u64,
+    pub max_supply: u64,
+    pub current_epoch: u64,
+    pub current_rewards: u64,
+    pub blocks_until_next_halving: u64,
+    pub average_block_time_secs: Option<u64>,
+    pub estimated_next_halving_ts: Option<u64>,
+}